@@ -0,0 +1,14 @@
+-- One row per item per cron run that fetched it
+CREATE TABLE IF NOT EXISTS item_snapshots (
+    item_id INTEGER NOT NULL,
+    fetched_at INTEGER NOT NULL, -- unix seconds
+    title TEXT,
+    url TEXT,
+    domain TEXT,
+    score INTEGER,
+    descendants INTEGER,
+    PRIMARY KEY (item_id, fetched_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_snapshots_fetched_at ON item_snapshots (fetched_at);
+CREATE INDEX IF NOT EXISTS idx_item_snapshots_domain ON item_snapshots (domain);
@@ -1,8 +1,6 @@
 use crate::config::{APP_USER_AGENT, LIMIT_DEFAULT};
 use serde::{Deserialize, Serialize};
-use worker::{
-    console_error, console_log, Error, Fetch, Method, Request, Response, Url,
-};
+use worker::{console_error, console_log, Error, Fetch, Method, Request, Response, Url};
 
 const HN_BASE_URL: &str = "https://hacker-news.firebaseio.com/v0/";
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -26,6 +24,8 @@ pub struct HackerNewsItem {
     pub score: Option<u64>,
     pub title: Option<String>,
     parts: Option<Vec<u64>>,
+    // Older cached values were written under the misspelled field name
+    #[serde(rename = "descendants", alias = "decendants")]
     pub decendants: Option<u64>,
 }
 
@@ -95,7 +95,7 @@ impl std::str::FromStr for LiveDataKey {
 }
 pub type LiveDataKey = LiveDataTypes;
 
-//// Unused LiveDataConfig type
+// Unused LiveDataConfig type
 // #[derive(Debug, Clone, Deserialize, Serialize)]
 // pub struct LiveDataConfig {
 //     endpoint: String,
@@ -109,85 +109,141 @@ pub type LiveDataKey = LiveDataTypes;
 
 // Get top stories with no shards
 pub async fn fetch_top_items(limit: Option<u16>) -> Result<Vec<HackerNewsItem>, Error> {
-    let limit = limit.unwrap_or(LIMIT_DEFAULT);
-    let ids = fetch_top_stories(Some(limit)).await?;
-    let items = fetch_items(&ids).await?;
-    Ok(items)
-}
-
-async fn fetch_json_response(base: &str, endpoint: &str) -> Result<Response, Error> {
-    let url = Url::parse_with_params(
-        format!("{}{}", base, endpoint).as_str(),
-        &[("print", "pretty")],
-    )?;
-    let mut req = Request::new(url.as_str(), Method::Get)?;
-    {
-        let headers = req.headers_mut()?;
-        headers.set("User-Agent", APP_USER_AGENT)?;
-        headers.set("Accept", "application/json")?;
-    }
-    let res = Fetch::Request(req).send().await?;
-    Ok(res)
+    HnClient::default().fetch_top_items(limit).await
 }
 
 pub async fn fetch_max_item() -> Result<u64, Error> {
-    let endpoint = "maxitem.json";
-    let mut res = fetch_json_response(HN_BASE_URL, endpoint).await?;
-    if !(200..300).contains(&res.status_code()) {
-        console_error!("[HN API] ❌ Failed to fetch max item id");
-        return Err(Error::RustError(
-            "Error message: Hacker News(HN) api wrapper failed to fetch max item".into(),
-        ));
-    }
-    let m = res.json::<u64>().await?;
-    Ok(m)
+    HnClient::default().fetch_max_item().await
 }
 
 pub async fn fetch_top_stories(limit: Option<u16>) -> Result<Vec<u64>, Error> {
-    let limit = limit.unwrap_or(LIMIT_DEFAULT);
-    let endpoint = "topstories.json";
-    let mut res = fetch_json_response(HN_BASE_URL, endpoint).await?;
-    if !(200..300).contains(&res.status_code()) {
-        console_error!(
-            "[HN API] ❌ Failed to fetch top stories with status:{}",
-            &res.status_code()
-        );
-        return Err(Error::RustError(
-            "Error message: Hacker News(HN) api wrapper failed to fetch top stories".into(),
-        ));
-    }
-    let mut v = res.json::<Vec<u64>>().await?;
-    v.truncate(limit.into());
-    Ok(v)
+    HnClient::default().fetch_top_stories(limit).await
 }
 
 pub async fn fetch_items(ids: &[u64]) -> Result<Vec<HackerNewsItem>, Error> {
-    let mut items = Vec::with_capacity(ids.len());
-    for &id in ids {
-        let item = fetch_item(id).await?;
-        items.push(item);
-    }
-    Ok(items)
+    HnClient::default().fetch_items(ids).await
 }
 
 pub async fn fetch_item(id: u64) -> Result<HackerNewsItem, Error> {
-    let endpoint = format!("item/{}.json", id);
-    let mut res = fetch_json_response(HN_BASE_URL, &endpoint).await?;
-    if !(200..300).contains(&res.status_code()) {
-        console_error!(
-            "[HN API] ❌ Failed to fetch item. id:{}, status:{}",
-            id,
-            &res.status_code()
-        );
-        return Err(Error::RustError(
-            format!(
+    HnClient::default().fetch_item(id).await
+}
+
+/// Firebase HN API client. The free functions above use the public endpoint.
+#[derive(Clone, Debug)]
+pub struct HnClient {
+    base: String,
+}
+
+impl Default for HnClient {
+    fn default() -> Self {
+        Self::new(HN_BASE_URL)
+    }
+}
+
+impl HnClient {
+    pub fn new(base: impl Into<String>) -> Self {
+        Self { base: base.into() }
+    }
+
+    pub async fn fetch_top_items(&self, limit: Option<u16>) -> Result<Vec<HackerNewsItem>, Error> {
+        let limit = limit.unwrap_or(LIMIT_DEFAULT);
+        let ids = self.fetch_top_stories(Some(limit)).await?;
+        let items = self.fetch_items(&ids).await?;
+        Ok(items)
+    }
+
+    async fn fetch_json_response(&self, endpoint: &str) -> Result<Response, Error> {
+        let url = Url::parse_with_params(
+            format!("{}{}", self.base, endpoint).as_str(),
+            &[("print", "pretty")],
+        )?;
+        let mut req = Request::new(url.as_str(), Method::Get)?;
+        {
+            let headers = req.headers_mut()?;
+            headers.set("User-Agent", APP_USER_AGENT)?;
+            headers.set("Accept", "application/json")?;
+        }
+        let res = Fetch::Request(req).send().await?;
+        Ok(res)
+    }
+
+    pub async fn fetch_max_item(&self) -> Result<u64, Error> {
+        let endpoint = "maxitem.json";
+        let mut res = self.fetch_json_response(endpoint).await?;
+        if !(200..300).contains(&res.status_code()) {
+            console_error!("[HN API] ❌ Failed to fetch max item id");
+            return Err(Error::RustError(
+                "Error message: Hacker News(HN) api wrapper failed to fetch max item".into(),
+            ));
+        }
+        let m = res.json::<u64>().await?;
+        Ok(m)
+    }
+
+    pub async fn fetch_top_stories(&self, limit: Option<u16>) -> Result<Vec<u64>, Error> {
+        let limit = limit.unwrap_or(LIMIT_DEFAULT);
+        let endpoint = "topstories.json";
+        let mut res = self.fetch_json_response(endpoint).await?;
+        if !(200..300).contains(&res.status_code()) {
+            console_error!(
+                "[HN API] ❌ Failed to fetch top stories with status:{}",
+                &res.status_code()
+            );
+            return Err(Error::RustError(
+                "Error message: Hacker News(HN) api wrapper failed to fetch top stories".into(),
+            ));
+        }
+        let mut v = res.json::<Vec<u64>>().await?;
+        v.truncate(limit.into());
+        Ok(v)
+    }
+
+    pub async fn fetch_items(&self, ids: &[u64]) -> Result<Vec<HackerNewsItem>, Error> {
+        let mut items = Vec::with_capacity(ids.len());
+        for &id in ids {
+            let item = self.fetch_item(id).await?;
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    pub async fn fetch_item(&self, id: u64) -> Result<HackerNewsItem, Error> {
+        let endpoint = format!("item/{}.json", id);
+        let mut res = self.fetch_json_response(&endpoint).await?;
+        if !(200..300).contains(&res.status_code()) {
+            console_error!(
+                "[HN API] ❌ Failed to fetch item. id:{}, status:{}",
+                id,
+                &res.status_code()
+            );
+            return Err(Error::RustError(format!(
                 "Error message: Hacker News(HN) api wrapper failed to fetch single item id:{}",
                 id
-            )
-            .into(),
-        ));
+            )));
+        }
+        let hn_item = res.json::<HackerNewsItem>().await?;
+        console_log!(
+            "[HN API] Fetch item from endpoint:{}. score:{:?}",
+            &endpoint,
+            &hn_item.score
+        );
+        Ok(hn_item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn item_reads_descendants_from_api_and_legacy_cache() {
+        let api = r#"{"id":8863,"by":"dhouston","time":1175714200,"score":104,"descendants":71,"type":"story"}"#;
+        let item = serde_json::from_str::<HackerNewsItem>(api).unwrap();
+        assert_eq!(item.decendants, Some(71));
+        let legacy = r#"{"item_id":8863,"by":"dhouston","timestamp":1175714200,"decendants":71}"#;
+        let item = serde_json::from_str::<HackerNewsItem>(legacy).unwrap();
+        assert_eq!(item.decendants, Some(71));
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["descendants"], 71);
     }
-    let hn_item = res.json::<HackerNewsItem>().await?;
-    console_log!("[HN API] Fetch item from endpoint:{}. score:{:?}", &endpoint, &hn_item.score);
-    Ok(hn_item)
 }
@@ -1,4 +1,6 @@
 pub mod hn;
 pub use hn::*;
 pub mod tg;
-pub use tg::*;
\ No newline at end of file
+pub use tg::*;
+pub mod webhook;
+pub use webhook::*;
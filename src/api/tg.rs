@@ -1,39 +1,334 @@
 use crate::config::APP_USER_AGENT;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use wasm_bindgen::JsValue;
-use worker::{console_log, Error, Fetch, Method, Request, RequestInit, Response, Url};
+use worker::{console_error, console_log, Error, Fetch, Method, Request, RequestInit, Url};
 
 const TG_BASE_URL: &str = "https://api.telegram.org/";
 
-pub async fn send_message(
-    token: &str,
-    chat_id: &str,
-    msg: &str,
-    reply_markup: Value,
-) -> Result<Response, Error> {
-    let url = Url::parse(format!("{}bot{}/sendMessage", TG_BASE_URL, token).as_str())?;
-    let payload = serde_json::json!({
-      "chat_id": chat_id,
-      "text": msg,
-      "parse_mode": "HTML",
-      "reply_markup": reply_markup,
-      "disable_web_page_preview": false,
-    });
-    let payload_str = payload.to_string();
-    let mut init = RequestInit::new();
-    {
-        init.with_method(Method::Post)
-            .with_body(Some(JsValue::from_str(&payload_str)));
-    }
-    let mut req = Request::new_with_init(url.as_str(), &init)?;
-    {
-        let headers = req.headers_mut()?;
-        headers.set("User-Agent", APP_USER_AGENT)?;
-        headers.set("Content-Type", "application/json")?;
-    }
-    let mut res = Fetch::Request(req).send().await?;
-    let status = res.status_code();
-    let body = &res.text().await.unwrap_or_default();
-    console_log!("[TG SendMessage] status:{} body:{}", status, body);
-    Ok(res)
+/// Every Bot API call answers with this envelope.
+/// See https://core.telegram.org/bots/api#making-requests
+#[derive(Debug, Clone, Deserialize)]
+pub struct TgEnvelope<T> {
+    pub ok: bool,
+    pub result: Option<T>,
+    pub description: Option<String>,
+    pub error_code: Option<u16>,
+    pub parameters: Option<ResponseParameters>,
+}
+
+impl<T> TgEnvelope<T> {
+    /// The result, or the error Telegram described. `status` stands in for a
+    /// missing `error_code`.
+    pub fn into_result(self, method: &'static str, status: u16) -> Result<T, TgError> {
+        match self {
+            TgEnvelope {
+                ok: true,
+                result: Some(result),
+                ..
+            } => Ok(result),
+            envelope => Err(TgError::Api {
+                method,
+                error_code: envelope.error_code.unwrap_or(status),
+                description: envelope.description.unwrap_or_default(),
+                parameters: envelope.parameters.unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ResponseParameters {
+    pub migrate_to_chat_id: Option<i64>,
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TgUser {
+    pub id: i64,
+    pub is_bot: bool,
+    pub first_name: String,
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TgChat {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub chat_type: String,
+    pub title: Option<String>,
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TgMessage {
+    pub message_id: i64,
+    pub date: i64,
+    pub chat: TgChat,
+    pub text: Option<String>,
+    pub caption: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum TgError {
+    /// Telegram answered `ok: false`
+    Api {
+        method: &'static str,
+        error_code: u16,
+        description: String,
+        parameters: ResponseParameters,
+    },
+    /// Transport or body decoding failure
+    Worker(Error),
+}
+
+impl TgError {
+    /// Seconds to wait before retrying, set on 429 Too Many Requests.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            TgError::Api { parameters, .. } => parameters.retry_after,
+            TgError::Worker(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TgError::Api {
+                method,
+                error_code,
+                description,
+                ..
+            } => write!(
+                f,
+                "Error message: Telegram {} failed. code:{} description:{}",
+                method, error_code, description
+            ),
+            TgError::Worker(e) => write!(f, "Error message: Telegram request failed. {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TgError {}
+
+impl From<Error> for TgError {
+    fn from(e: Error) -> Self {
+        TgError::Worker(e)
+    }
+}
+
+impl From<serde_json::Error> for TgError {
+    fn from(e: serde_json::Error) -> Self {
+        TgError::Worker(e.into())
+    }
+}
+
+impl From<TgError> for Error {
+    fn from(e: TgError) -> Self {
+        match e {
+            TgError::Worker(e) => e,
+            api => Error::RustError(api.to_string()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TelegramClient {
+    token: String,
+}
+
+impl TelegramClient {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        payload: &Value,
+    ) -> Result<T, TgError> {
+        let url = Url::parse(format!("{}bot{}/{}", TG_BASE_URL, self.token, method).as_str())
+            .map_err(Error::from)?;
+        // Optional parameters are passed as null, Telegram expects them omitted
+        let payload_str = match payload {
+            Value::Object(m) => Value::Object(
+                m.iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+            )
+            .to_string(),
+            v => v.to_string(),
+        };
+        let mut init = RequestInit::new();
+        {
+            init.with_method(Method::Post)
+                .with_body(Some(JsValue::from_str(&payload_str)));
+        }
+        let mut req = Request::new_with_init(url.as_str(), &init)?;
+        {
+            let headers = req.headers_mut()?;
+            headers.set("User-Agent", APP_USER_AGENT)?;
+            headers.set("Content-Type", "application/json")?;
+        }
+        let mut res = Fetch::Request(req).send().await?;
+        let status = res.status_code();
+        let body = res.text().await?;
+        let envelope = serde_json::from_str::<TgEnvelope<T>>(&body)?;
+        let result = envelope.into_result(method, status);
+        match result {
+            Ok(_) => console_log!("[TG {}] status:{} ok:true", method, status),
+            Err(_) => console_error!("[TG {}] ❌ status:{} body:{}", method, status, body),
+        }
+        result
+    }
+
+    pub async fn get_me(&self) -> Result<TgUser, TgError> {
+        self.call("getMe", &serde_json::json!({})).await
+    }
+
+    pub async fn send_message(
+        &self,
+        chat_id: &str,
+        msg: &str,
+        reply_markup: Option<Value>,
+    ) -> Result<TgMessage, TgError> {
+        let payload = serde_json::json!({
+          "chat_id": chat_id,
+          "text": msg,
+          "parse_mode": "HTML",
+          "reply_markup": reply_markup,
+          "disable_web_page_preview": false,
+        });
+        self.call("sendMessage", &payload).await
+    }
+
+    pub async fn edit_message_text(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+        msg: &str,
+        reply_markup: Option<Value>,
+    ) -> Result<TgMessage, TgError> {
+        let payload = serde_json::json!({
+          "chat_id": chat_id,
+          "message_id": message_id,
+          "text": msg,
+          "parse_mode": "HTML",
+          "reply_markup": reply_markup,
+        });
+        self.call("editMessageText", &payload).await
+    }
+
+    pub async fn delete_message(&self, chat_id: &str, message_id: i64) -> Result<bool, TgError> {
+        let payload = serde_json::json!({
+          "chat_id": chat_id,
+          "message_id": message_id,
+        });
+        self.call("deleteMessage", &payload).await
+    }
+
+    /// `photo` is an HTTP url Telegram downloads itself, or a file_id.
+    pub async fn send_photo(
+        &self,
+        chat_id: &str,
+        photo: &str,
+        caption: &str,
+        reply_markup: Option<Value>,
+    ) -> Result<TgMessage, TgError> {
+        let payload = serde_json::json!({
+          "chat_id": chat_id,
+          "photo": photo,
+          "caption": caption,
+          "parse_mode": "HTML",
+          "reply_markup": reply_markup,
+        });
+        self.call("sendPhoto", &payload).await
+    }
+
+    pub async fn pin_chat_message(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+        disable_notification: bool,
+    ) -> Result<bool, TgError> {
+        let payload = serde_json::json!({
+          "chat_id": chat_id,
+          "message_id": message_id,
+          "disable_notification": disable_notification,
+        });
+        self.call("pinChatMessage", &payload).await
+    }
+
+    pub async fn set_webhook(
+        &self,
+        url: &str,
+        secret_token: Option<&str>,
+    ) -> Result<bool, TgError> {
+        let payload = serde_json::json!({
+          "url": url,
+          "secret_token": secret_token,
+        });
+        self.call("setWebhook", &payload).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse<T: DeserializeOwned>(body: &str, status: u16) -> Result<T, TgError> {
+        serde_json::from_str::<TgEnvelope<T>>(body)
+            .unwrap()
+            .into_result("sendMessage", status)
+    }
+
+    #[test]
+    fn ok_envelope_yields_result() {
+        let body = r#"{"ok":true,"result":{"message_id":42,"date":1700000000,
+            "chat":{"id":-1001,"type":"channel","title":"HN"},"text":"hi"}}"#;
+        let msg = parse::<TgMessage>(body, 200).unwrap();
+        assert_eq!(msg.message_id, 42);
+        assert_eq!(msg.chat.chat_type, "channel");
+        assert_eq!(msg.text.as_deref(), Some("hi"));
+        assert!(parse::<bool>(r#"{"ok":true,"result":true}"#, 200).unwrap());
+    }
+
+    #[test]
+    fn error_envelope_keeps_code_and_description() {
+        let body = r#"{"ok":false,"error_code":400,"description":"Bad Request: chat not found"}"#;
+        let err = parse::<TgMessage>(body, 400).unwrap_err();
+        assert!(err.retry_after().is_none());
+        match err {
+            TgError::Api {
+                method,
+                error_code,
+                description,
+                ..
+            } => {
+                assert_eq!(method, "sendMessage");
+                assert_eq!(error_code, 400);
+                assert_eq!(description, "Bad Request: chat not found");
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+        // Without error_code the HTTP status stands in
+        match parse::<TgMessage>(r#"{"ok":false}"#, 502).unwrap_err() {
+            TgError::Api { error_code, .. } => assert_eq!(error_code, 502),
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn too_many_requests_carries_retry_after() {
+        let body = r#"{"ok":false,"error_code":429,
+            "description":"Too Many Requests: retry after 17",
+            "parameters":{"retry_after":17}}"#;
+        let err = parse::<TgMessage>(body, 429).unwrap_err();
+        assert_eq!(err.retry_after(), Some(17));
+        assert!(err.to_string().contains("code:429"));
+    }
 }
@@ -0,0 +1,65 @@
+use crate::config::APP_USER_AGENT;
+use serde_json::Value;
+use wasm_bindgen::JsValue;
+use worker::{console_log, Error, Fetch, Method, Request, RequestInit, Response, Url};
+
+// Generic JSON POST used by webhook style sinks (Discord, Slack, custom webhook)
+pub async fn post_json(
+    url: &str,
+    payload: &Value,
+    extra_headers: &[(&str, &str)],
+) -> Result<Response, Error> {
+    let payload_str = payload.to_string();
+    post_raw(url, &payload_str, extra_headers).await
+}
+
+// Generic JSON PUT, e.g. Matrix client-server `send` endpoint
+pub async fn put_json(
+    url: &str,
+    payload: &Value,
+    extra_headers: &[(&str, &str)],
+) -> Result<Response, Error> {
+    let payload_str = payload.to_string();
+    send_raw(Method::Put, url, &payload_str, extra_headers).await
+}
+
+// Post an already serialized body, e.g. when the exact bytes are signed
+pub async fn post_raw(
+    url: &str,
+    body: &str,
+    extra_headers: &[(&str, &str)],
+) -> Result<Response, Error> {
+    send_raw(Method::Post, url, body, extra_headers).await
+}
+
+async fn send_raw(
+    method: Method,
+    url: &str,
+    body: &str,
+    extra_headers: &[(&str, &str)],
+) -> Result<Response, Error> {
+    let url = Url::parse(url)?;
+    let method_str = method.to_string();
+    let mut init = RequestInit::new();
+    {
+        init.with_method(method)
+            .with_body(Some(JsValue::from_str(body)));
+    }
+    let mut req = Request::new_with_init(url.as_str(), &init)?;
+    {
+        let headers = req.headers_mut()?;
+        headers.set("User-Agent", APP_USER_AGENT)?;
+        headers.set("Content-Type", "application/json")?;
+        for (k, v) in extra_headers {
+            headers.set(k, v)?;
+        }
+    }
+    let res = Fetch::Request(req).send().await?;
+    console_log!(
+        "[Webhook] {} host:{} status:{}",
+        method_str,
+        url.host_str().unwrap_or_default(),
+        res.status_code()
+    );
+    Ok(res)
+}
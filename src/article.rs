@@ -0,0 +1,320 @@
+use std::{cell::RefCell, time::Duration};
+
+use futures_util::StreamExt;
+use lol_html::{element, text, HtmlRewriter, Settings};
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::{
+    api::hn::HackerNewsItem,
+    config::{APP_USER_AGENT, ARTICLE_MAX_BYTES, ARTICLE_TEXT_MAX_BYTES, ARTICLE_TIMEOUT_MS},
+    keys::ARTICLE_PREFIX,
+    kvm::{KVManager, KVMeta},
+    utils::tools::{strip_html, truncate_graphemes},
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Article {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    /// Readability-style main text, paragraphs joined by blank lines
+    pub text: String,
+}
+
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    // Inside nav, header, footer or aside
+    boilerplate: bool,
+}
+
+#[derive(Default)]
+struct Extract {
+    title: String,
+    og_title: Option<String>,
+    description: Option<String>,
+    og_description: Option<String>,
+    image: Option<String>,
+    main_paragraphs: Vec<Paragraph>,
+    paragraphs: Vec<Paragraph>,
+}
+
+/// Article for an item, from the KV cache or fetched and extracted.
+/// Returns `None` for self posts and pages that are not HTML.
+pub async fn fetch_article_cached(
+    kvm: &KVManager,
+    item: &HackerNewsItem,
+) -> Result<Option<Article>> {
+    let Some(url) = item.url.as_deref() else {
+        return Ok(None);
+    };
+    let key = article_key(item.item_id);
+    if let Some(cached) = kvm.get_json::<Article>(&key).await? {
+        console_log!("[Article] Hit cache for id:{}", item.item_id);
+        return Ok(Some(cached));
+    }
+    let Some(article) = fetch_article(url).await? else {
+        return Ok(None);
+    };
+    kvm.create(&key, serde_json::to_string(&article)?, None::<KVMeta>, None)
+        .await?;
+    Ok(Some(article))
+}
+
+/// Preview image of an already cached article, without fetching the page.
+pub async fn cached_article_image(kv: &KvStore, item_id: u64) -> Result<Option<String>> {
+    let cached: Option<Article> = kv.get(&article_key(item_id)).json().await?;
+    Ok(cached.and_then(|a| a.image))
+}
+
+fn article_key(item_id: u64) -> String {
+    format!("{}{}", ARTICLE_PREFIX, item_id)
+}
+
+/// Fetch a page within `ARTICLE_TIMEOUT_MS` and `ARTICLE_MAX_BYTES` and
+/// `extract` its metadata and main text.
+pub async fn fetch_article(url: &str) -> Result<Option<Article>> {
+    let url = Url::parse(url)?;
+    let controller = AbortController::default();
+    let signal = controller.signal();
+    wasm_bindgen_futures::spawn_local(async move {
+        Delay::from(Duration::from_millis(ARTICLE_TIMEOUT_MS)).await;
+        controller.abort();
+    });
+    let mut req = Request::new(url.as_str(), Method::Get)?;
+    {
+        let headers = req.headers_mut()?;
+        headers.set("User-Agent", APP_USER_AGENT)?;
+        headers.set("Accept", "text/html,application/xhtml+xml")?;
+    }
+    let mut res = Fetch::Request(req).send_with_signal(&signal).await?;
+    if !(200..300).contains(&res.status_code()) {
+        console_warn!(
+            "[Article] ⚠️ Fetch host:{} status:{}",
+            url.host_str().unwrap_or_default(),
+            res.status_code()
+        );
+        return Ok(None);
+    }
+    let content_type = res.headers().get("Content-Type")?.unwrap_or_default();
+    if !content_type.contains("html") {
+        console_log!("[Article] Skip non HTML content-type:{}", content_type);
+        return Ok(None);
+    }
+
+    let mut html = Vec::new();
+    let mut stream = res.stream()?;
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) if signal.aborted() => {
+                console_warn!("[Article] ⚠️ Timeout reading host:{}. {}", url, e);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        html.extend_from_slice(&chunk);
+        if html.len() >= ARTICLE_MAX_BYTES {
+            console_warn!(
+                "[Article] ⚠️ Stop reading at {} bytes for {}",
+                html.len(),
+                url
+            );
+            break;
+        }
+    }
+    let article = extract(&html, &url);
+    console_log!(
+        "[Article] Extracted {} ({} bytes read, {} bytes text)",
+        url,
+        html.len(),
+        article.text.len()
+    );
+    Ok(Some(article))
+}
+
+/// Metadata and main text of an HTML page, `base` resolves a relative
+/// og:image. Paragraphs inside article or main are preferred over the rest of
+/// the page, paragraphs in nav, header, footer or aside and short fragments
+/// are dropped. Only the first `ARTICLE_MAX_BYTES` are read.
+pub fn extract(html: &[u8], base: &Url) -> Article {
+    const BOILERPLATE: &str = "nav p, header p, footer p, aside p";
+    const MAIN_BOILERPLATE: &str = "article nav p, article header p, article footer p, \
+        article aside p, main nav p, main header p, main footer p, main aside p";
+    let extract = RefCell::new(Extract::default());
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                text!("head > title", |t| {
+                    extract.borrow_mut().title.push_str(t.as_str());
+                    Ok(())
+                }),
+                element!("meta[property='og:title']", |el| {
+                    extract.borrow_mut().og_title = el.get_attribute("content");
+                    Ok(())
+                }),
+                element!("meta[name='description']", |el| {
+                    extract.borrow_mut().description = el.get_attribute("content");
+                    Ok(())
+                }),
+                element!("meta[property='og:description']", |el| {
+                    extract.borrow_mut().og_description = el.get_attribute("content");
+                    Ok(())
+                }),
+                element!("meta[property='og:image']", |el| {
+                    extract.borrow_mut().image = el.get_attribute("content");
+                    Ok(())
+                }),
+                // Each <p> start opens a paragraph, its text chunks append to
+                // it. Handlers run in this order, so the boilerplate ones mark
+                // the paragraph just opened
+                element!("article p, main p", |_| {
+                    extract
+                        .borrow_mut()
+                        .main_paragraphs
+                        .push(Paragraph::default());
+                    Ok(())
+                }),
+                element!(MAIN_BOILERPLATE, |_| {
+                    if let Some(p) = extract.borrow_mut().main_paragraphs.last_mut() {
+                        p.boilerplate = true;
+                    }
+                    Ok(())
+                }),
+                text!("article p, main p", |t| {
+                    if let Some(p) = extract.borrow_mut().main_paragraphs.last_mut() {
+                        p.text.push_str(t.as_str());
+                    }
+                    Ok(())
+                }),
+                element!("p", |_| {
+                    extract.borrow_mut().paragraphs.push(Paragraph::default());
+                    Ok(())
+                }),
+                element!(BOILERPLATE, |_| {
+                    if let Some(p) = extract.borrow_mut().paragraphs.last_mut() {
+                        p.boilerplate = true;
+                    }
+                    Ok(())
+                }),
+                text!("p", |t| {
+                    if let Some(p) = extract.borrow_mut().paragraphs.last_mut() {
+                        p.text.push_str(t.as_str());
+                    }
+                    Ok(())
+                }),
+            ],
+            ..Settings::new()
+        },
+        |_: &[u8]| {},
+    );
+    let html = &html[..html.len().min(ARTICLE_MAX_BYTES)];
+    // Handlers never fail, an error leaves what was extracted so far
+    let _ = rewriter.write(html).and_then(|_| rewriter.end());
+
+    let e = extract.into_inner();
+    let main = e
+        .main_paragraphs
+        .into_iter()
+        .filter(|p| !p.boilerplate)
+        .collect::<Vec<_>>();
+    let paragraphs = if main.is_empty() { e.paragraphs } else { main };
+    let text = truncate_graphemes(
+        &paragraphs
+            .iter()
+            .filter(|p| !p.boilerplate)
+            .map(|p| strip_html(&p.text))
+            // Short fragments are usually bylines, captions or cookie banners
+            .filter(|p| p.len() >= 40)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        ARTICLE_TEXT_MAX_BYTES,
+        |g| g.len(),
+    );
+    let title = e
+        .og_title
+        .or(Some(e.title))
+        .map(|t| strip_html(&t))
+        .filter(|t| !t.is_empty());
+    let image = e
+        .image
+        .and_then(|i| base.join(&i).ok())
+        .map(|u| u.to_string());
+    Article {
+        title,
+        description: e.og_description.or(e.description).map(|d| strip_html(&d)),
+        image,
+        text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG: &str = "This paragraph is long enough to count as article text.";
+
+    fn base() -> Url {
+        Url::parse("https://example.com/blog/post.html").unwrap()
+    }
+
+    #[test]
+    fn reads_metadata_and_resolves_relative_image() {
+        let html = r#"<html><head><title>Plain &amp; title</title>
+            <meta property="og:title" content="OG title">
+            <meta name="description" content="Plain description">
+            <meta property="og:image" content="../img/cover.png">
+            </head><body></body></html>"#;
+        let article = extract(html.as_bytes(), &base());
+        assert_eq!(article.title.as_deref(), Some("OG title"));
+        assert_eq!(article.description.as_deref(), Some("Plain description"));
+        assert_eq!(
+            article.image.as_deref(),
+            Some("https://example.com/img/cover.png")
+        );
+        let absolute = r#"<meta property="og:image" content="https://cdn.example.org/a.jpg">"#;
+        assert_eq!(
+            extract(absolute.as_bytes(), &base()).image.as_deref(),
+            Some("https://cdn.example.org/a.jpg")
+        );
+    }
+
+    #[test]
+    fn prefers_main_text_and_skips_boilerplate() {
+        let html = format!(
+            "<nav><p>Home · Archive · About · Subscribe to the newsletter today</p></nav>\
+             <p>Outside the article but still a long enough paragraph.</p>\
+             <article><header><p>Posted by someone on some day, read in five minutes</p></header>\
+             <p>First: {LONG}</p><p>Short byline</p><p>Second: <b>{LONG}</b></p>\
+             <footer><p>Share this post on every social network you can find</p></footer></article>\
+             <footer><p>Copyright and a long list of legal text nobody reads at all</p></footer>"
+        );
+        let article = extract(html.as_bytes(), &base());
+        assert_eq!(article.text, format!("First: {LONG}\n\nSecond: {LONG}"));
+    }
+
+    #[test]
+    fn falls_back_to_page_paragraphs_without_boilerplate() {
+        let html = format!(
+            "<header><p>Site header with a long tagline that is not content</p></header>\
+             <div><p>{LONG}</p></div>\
+             <aside><p>Related posts you might like, a long list of links</p></aside>"
+        );
+        assert_eq!(extract(html.as_bytes(), &base()).text, LONG);
+    }
+
+    #[test]
+    fn caps_text_and_input_size() {
+        let paragraph = format!("<p>{}</p>", "word ".repeat(100));
+        let html = format!("<article>{}</article>", paragraph.repeat(100));
+        let text = extract(html.as_bytes(), &base()).text;
+        assert!(text.len() <= ARTICLE_TEXT_MAX_BYTES);
+        assert!(text.len() > ARTICLE_TEXT_MAX_BYTES - 600);
+
+        // Anything past ARTICLE_MAX_BYTES is never read
+        let mut html = " ".repeat(ARTICLE_MAX_BYTES).into_bytes();
+        html.extend_from_slice(format!("<p>{LONG}</p>").as_bytes());
+        assert_eq!(extract(&html, &base()).text, "");
+    }
+}
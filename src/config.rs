@@ -1,9 +1,319 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use serde::{Serialize, Serializer};
+use worker::{Env, Url};
+
+use crate::{keys::reserved_overlap, utils::tools::env_string};
+
 pub const APP_NAME: &str = "hacker-news-worker-rs";
 pub const APP_VERSION: &str = "0.1.0";
 pub const APP_USER_AGENT: &str = "Cloudflare Worker - hacker-news-worker-rs/0.1.0";
 
 pub const LIMIT_DEFAULT: u16 = 20;
-pub const KV_TTL_KEY: &str = "TTL";
 pub const KV_TTL_VAL: u64 = 86400;
+// Cloudflare KV caps serialized metadata at 1024 bytes
+pub const KV_META_MAX_BYTES: usize = 1024;
+pub const MAX_DELIVERY_ATTEMPTS: u8 = 3;
+// Delivery records keep post references (e.g. at:// uris) for a week
+pub const DELIVERY_TTL: u64 = 7 * 86400;
 pub const MIN_SCORE_DEFAULT: u64 = 150;
 pub const UNIX_TIME_DEFAULT: u64 = 0;
+
+pub const LLM_MAX_TOKENS: u32 = 256;
+// Leaves room for the other KVMeta fields within KV_META_MAX_BYTES
+pub const LLM_SUMMARY_MAX_BYTES: usize = 480;
+pub const LLM_SCORE_MAX: f32 = 10.0;
+pub const LLM_MIN_SCORE_DEFAULT: f32 = 5.0;
+// Candidates stay on the top list for about a day, each is scored once meanwhile
+pub const LLM_SCORE_TTL: u64 = 2 * 86400;
+
+pub const ARTICLE_MAX_BYTES: usize = 2 * 1024 * 1024;
+pub const ARTICLE_TIMEOUT_MS: u64 = 5000;
+pub const ARTICLE_TEXT_MAX_BYTES: usize = 8 * 1024;
+pub const SHORT_LINK_BASE_DEFAULT: &str = "https://readhacker.news";
+// Click counters outlive the item cache so stats stay readable for a month
+pub const CLICKS_TTL: u64 = 30 * 86400;
+// Clicks are batched per isolate this long, within the 30s a fetch may
+// keep running after its response
+pub const CLICKS_FLUSH_SECS: u64 = 10;
+// Signed admin requests older or newer than this are rejected as replays
+pub const ADMIN_SIGNATURE_WINDOW_SECS: u64 = 300;
+pub const ADMIN_BODY_MAX_BYTES: usize = 64 * 1024;
+pub const RUN_LOG_MAX: usize = 50;
+// A cron invocation may run up to 15 minutes, a lease outliving that is stale
+pub const JOB_LEASE_SECS: u64 = 15 * 60;
+pub const KV_MIN_TTL_SECS: u64 = 60;
+// Well past the item cache TTL, an item is off the top list by then
+pub const DEDUP_RETENTION_SECS: u64 = 7 * 86400;
+pub const STATUS_RUNS_DEFAULT: usize = 10;
+// Rows per week or domains returned by the public /api/history routes
+pub const HISTORY_LIMIT_MAX: u32 = 100;
+// A day of 10 minute cron runs per item in the top list
+pub const TRAJECTORY_RETENTION_SECS: u64 = 86400;
+pub const TRAJECTORY_POINTS_MAX: usize = 144;
+// Velocity is measured over the last 20 minutes, and over no less than 5
+// so two runs close together do not look like a spike
+pub const RISING_WINDOW_SECS: u64 = 20 * 60;
+pub const RISING_MIN_SPAN_SECS: u64 = 5 * 60;
+pub const RISING_POINTS_PER_MIN_DEFAULT: f64 = 2.0;
+pub const RISING_RANK_CLIMB_DEFAULT: u32 = 20;
+// A rank climb only counts once the item reaches the front of the list
+pub const RISING_TOP_RANK: u32 = 10;
+// Ranks are tracked this deep from the id list alone (one request), only
+// the first `TOP_LIMIT` items are fetched with scores
+pub const TRAJECTORY_DEPTH: u16 = 60;
+
+pub const KV_BINDING_DEFAULT: &str = "HACKER_NEWS_WORKER_RS";
+pub const KV_PREFIX_DEFAULT: &str = "HN-";
+const LLM_PROVIDERS: [&str; 3] = ["workers_ai", "openai", "mock"];
+
+/// A secret value. Serialized and debug-printed as `<redacted>`.
+#[derive(Clone)]
+pub struct Redacted(String);
+
+impl Redacted {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl Serialize for Redacted {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("<redacted>")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupBackend {
+    /// Listed `HN-` keys, eventually consistent across locations
+    Kv,
+    /// The `DEDUP_INDEX` Durable Object, strongly consistent
+    DurableObject,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TelegramConfig {
+    pub bot_token: Redacted,
+    pub chat_id: String,
+    pub preview_images: bool,
+}
+
+/// Early alert rule for fast-rising stories, `0` disables a threshold.
+#[derive(Clone, Debug, Serialize)]
+pub struct RisingRule {
+    pub points_per_min: f64,
+    pub rank_climb: u32,
+}
+
+/// Effective configuration: Env vars and secrets over the defaults above.
+/// Sink-specific settings (Discord, Slack, ...) stay with their notifiers.
+#[derive(Clone, Debug, Serialize)]
+pub struct Config {
+    pub kv_binding: String,
+    pub kv_prefix: String,
+    pub kv_ttl: u64,
+    pub top_limit: u16,
+    pub min_score: u64,
+    pub min_time: u64,
+    pub rising: RisingRule,
+    pub link_base: String,
+    pub dedup: DedupBackend,
+    pub telegram: Option<TelegramConfig>,
+    pub email_from: Option<String>,
+    pub email_to: Option<String>,
+    pub llm_provider: Option<String>,
+    pub llm_min_score: f32,
+    pub admin_token: Option<Redacted>,
+    pub admin_hmac_secret: Option<Redacted>,
+}
+
+/// Every missing or invalid field found while loading `Config`.
+#[derive(Clone, Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error message: invalid config. {}", self.0.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ConfigError> for worker::Error {
+    fn from(e: ConfigError) -> Self {
+        worker::Error::RustError(e.to_string())
+    }
+}
+
+impl Config {
+    /// Invalid fields fall back to their defaults so routes that do not depend
+    /// on them keep working; every problem found is returned alongside.
+    pub fn from_env(env: &Env) -> (Arc<Self>, Option<ConfigError>) {
+        let mut errors = Vec::new();
+        let kv_binding = env_string(env, "KV_BINDING").unwrap_or(KV_BINDING_DEFAULT.to_string());
+        if env.kv(&kv_binding).is_err() {
+            errors.push(format!("KV binding {} is missing", kv_binding));
+        }
+        let mut link_base =
+            env_string(env, "SHORT_LINK_BASE").unwrap_or(SHORT_LINK_BASE_DEFAULT.to_string());
+        match Url::parse(&link_base) {
+            Ok(u) if matches!(u.scheme(), "http" | "https") => {}
+            _ => {
+                errors.push(format!(
+                    "SHORT_LINK_BASE {} is not an http(s) url",
+                    link_base
+                ));
+                link_base = SHORT_LINK_BASE_DEFAULT.to_string();
+            }
+        }
+        let dedup = match env_string(env, "DEDUP_BACKEND").as_deref() {
+            None | Some("kv") => DedupBackend::Kv,
+            Some("durable_object") => {
+                if env.durable_object("DEDUP_INDEX").is_err() {
+                    errors.push(
+                        "DEDUP_BACKEND durable_object needs the DEDUP_INDEX binding".to_string(),
+                    );
+                    DedupBackend::Kv
+                } else {
+                    DedupBackend::DurableObject
+                }
+            }
+            Some(other) => {
+                errors.push(format!(
+                    "DEDUP_BACKEND {} is not one of [\"kv\", \"durable_object\"]",
+                    other
+                ));
+                DedupBackend::Kv
+            }
+        };
+        let telegram = match (
+            env_string(env, "TG_BOT_TOKEN"),
+            env_string(env, "TG_CHAT_ID"),
+        ) {
+            (Some(token), Some(chat_id)) => Some(TelegramConfig {
+                bot_token: Redacted(token),
+                chat_id,
+                preview_images: parse_var(env, "TG_PREVIEW_IMAGES", false, &mut errors),
+            }),
+            (Some(_), None) => {
+                errors.push("TG_CHAT_ID is missing while TG_BOT_TOKEN is set".to_string());
+                None
+            }
+            (None, _) => None,
+        };
+        let llm_provider = env_string(env, "LLM_PROVIDER").filter(|p| {
+            let known = LLM_PROVIDERS.contains(&p.as_str());
+            if !known {
+                errors.push(format!(
+                    "LLM_PROVIDER {} is not one of {:?}",
+                    p, LLM_PROVIDERS
+                ));
+            }
+            known
+        });
+        let mut llm_min_score = parse_var(env, "LLM_MIN_SCORE", LLM_MIN_SCORE_DEFAULT, &mut errors);
+        if !(0.0..=LLM_SCORE_MAX).contains(&llm_min_score) {
+            errors.push(format!(
+                "LLM_MIN_SCORE {} is outside 0..={}",
+                llm_min_score, LLM_SCORE_MAX
+            ));
+            llm_min_score = LLM_MIN_SCORE_DEFAULT;
+        }
+        let mut kv_prefix = env_string(env, "KV_PREFIX").unwrap_or(KV_PREFIX_DEFAULT.to_string());
+        if let Some(key) = reserved_overlap(&kv_prefix) {
+            errors.push(format!(
+                "KV_PREFIX \"{}\" overlaps the reserved KV key {}",
+                kv_prefix, key
+            ));
+            kv_prefix = KV_PREFIX_DEFAULT.to_string();
+        }
+        let config = Self {
+            kv_binding,
+            kv_prefix,
+            kv_ttl: parse_var(env, "KV_TTL", KV_TTL_VAL, &mut errors),
+            top_limit: parse_var(env, "TOP_LIMIT", LIMIT_DEFAULT, &mut errors),
+            min_score: parse_var(env, "MIN_SCORE", MIN_SCORE_DEFAULT, &mut errors),
+            min_time: parse_var(env, "MIN_TIME", UNIX_TIME_DEFAULT, &mut errors),
+            rising: RisingRule {
+                points_per_min: parse_var(
+                    env,
+                    "RISING_POINTS_PER_MIN",
+                    RISING_POINTS_PER_MIN_DEFAULT,
+                    &mut errors,
+                ),
+                rank_climb: parse_var(
+                    env,
+                    "RISING_RANK_CLIMB",
+                    RISING_RANK_CLIMB_DEFAULT,
+                    &mut errors,
+                ),
+            },
+            link_base,
+            dedup,
+            telegram,
+            email_from: env_string(env, "EMAIL_FROM"),
+            email_to: env_string(env, "EMAIL_TO"),
+            llm_provider,
+            llm_min_score,
+            admin_token: env_string(env, "ADMIN_TOKEN").map(Redacted),
+            admin_hmac_secret: env_string(env, "ADMIN_HMAC_SECRET").map(Redacted),
+        };
+        let error = (!errors.is_empty()).then_some(ConfigError(errors));
+        (Arc::new(config), error)
+    }
+}
+
+fn parse_var<T>(env: &Env, name: &str, default: T, errors: &mut Vec<String>) -> T
+where
+    T: FromStr + fmt::Display,
+{
+    match env_string(env, name) {
+        None => default,
+        Some(v) => v.parse().unwrap_or_else(|_| {
+            errors.push(format!(
+                "{} {:?} is invalid, default is {}",
+                name, v, default
+            ));
+            default
+        }),
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// Defaults with a Telegram sink, as `from_env` builds them.
+    pub(crate) fn mock() -> Self {
+        Self {
+            kv_binding: KV_BINDING_DEFAULT.to_string(),
+            kv_prefix: KV_PREFIX_DEFAULT.to_string(),
+            kv_ttl: KV_TTL_VAL,
+            top_limit: LIMIT_DEFAULT,
+            min_score: MIN_SCORE_DEFAULT,
+            min_time: UNIX_TIME_DEFAULT,
+            rising: RisingRule {
+                points_per_min: RISING_POINTS_PER_MIN_DEFAULT,
+                rank_climb: RISING_RANK_CLIMB_DEFAULT,
+            },
+            link_base: SHORT_LINK_BASE_DEFAULT.to_string(),
+            dedup: DedupBackend::Kv,
+            telegram: Some(TelegramConfig {
+                bot_token: Redacted("token".to_string()),
+                chat_id: "@hn_channel".to_string(),
+                preview_images: false,
+            }),
+            email_from: None,
+            email_to: None,
+            llm_provider: None,
+            llm_min_score: LLM_MIN_SCORE_DEFAULT,
+            admin_token: None,
+            admin_hmac_secret: None,
+        }
+    }
+}
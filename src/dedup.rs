@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::config::{DedupBackend, DEDUP_RETENTION_SECS};
+
+pub const DEDUP_BINDING: &str = "DEDUP_INDEX";
+// One object holds the whole index, so every check-and-insert is serialized
+const DEDUP_OBJECT_NAME: &str = "notified-items";
+const IDS_KEY: &str = "ids";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IdsBody {
+    ids: Vec<u64>,
+}
+
+/// Authoritative set of notified item ids. Ids older than
+/// `DEDUP_RETENTION_SECS` are pruned on each write.
+#[durable_object]
+pub struct DedupIndex {
+    state: State,
+}
+
+impl DurableObject for DedupIndex {
+    fn new(state: State, _env: Env) -> Self {
+        Self { state }
+    }
+
+    async fn fetch(&self, mut req: Request) -> Result<Response> {
+        let body = req.json::<IdsBody>().await?;
+        let storage = self.state.storage();
+        // id -> claimed at (unix secs)
+        let mut ids = storage
+            .get::<BTreeMap<String, u64>>(IDS_KEY)
+            .await?
+            .unwrap_or_default();
+        match req.path().as_str() {
+            // Read only, used by dry runs
+            "/contains" => Response::from_json(&IdsBody {
+                ids: body
+                    .ids
+                    .into_iter()
+                    .filter(|id| ids.contains_key(&id.to_string()))
+                    .collect(),
+            }),
+            // Check-and-insert, answers the ids this call inserted
+            "/claim" => {
+                let now = Date::now().as_millis() / 1000;
+                ids.retain(|_, at| now.saturating_sub(*at) < DEDUP_RETENTION_SECS);
+                let claimed = body
+                    .ids
+                    .into_iter()
+                    .filter(|id| ids.insert(id.to_string(), now).is_none())
+                    .collect::<Vec<u64>>();
+                storage.put(IDS_KEY, &ids).await?;
+                Response::from_json(&IdsBody { ids: claimed })
+            }
+            // Undo a claim whose pending entry could not be written
+            "/release" => {
+                let released = body
+                    .ids
+                    .into_iter()
+                    .filter(|id| ids.remove(&id.to_string()).is_some())
+                    .collect::<Vec<u64>>();
+                storage.put(IDS_KEY, &ids).await?;
+                Response::from_json(&IdsBody { ids: released })
+            }
+            p => Response::error(format!("Unknown dedup path {}", p), 404),
+        }
+    }
+}
+
+/// Where the job checks whether an item was already notified.
+pub enum Dedup {
+    /// The `HN-` keys listed from KV, eventually consistent
+    Kv,
+    DurableObject(ObjectNamespace),
+}
+
+impl Dedup {
+    pub fn from_env(env: &Env, backend: DedupBackend) -> Result<Self> {
+        match backend {
+            DedupBackend::Kv => Ok(Dedup::Kv),
+            DedupBackend::DurableObject => {
+                Ok(Dedup::DurableObject(env.durable_object(DEDUP_BINDING)?))
+            }
+        }
+    }
+
+    /// Insert `ids` and return those that were not in the index yet. The KV
+    /// path already filtered on listed keys and keeps every id.
+    pub async fn claim(&self, ids: &[u64]) -> Result<Vec<u64>> {
+        match self {
+            Dedup::Kv => Ok(ids.to_vec()),
+            Dedup::DurableObject(ns) => call(ns, "claim", ids).await,
+        }
+    }
+
+    /// Drop `ids` claimed by this run again, so a later run can post them.
+    pub async fn release(&self, ids: &[u64]) -> Result<()> {
+        match self {
+            Dedup::Kv => Ok(()),
+            Dedup::DurableObject(ns) => call(ns, "release", ids).await.map(|_| ()),
+        }
+    }
+
+    /// The `ids` already in the index, without inserting.
+    pub async fn contains(&self, ids: &[u64]) -> Result<Vec<u64>> {
+        match self {
+            Dedup::Kv => Ok(Vec::new()),
+            Dedup::DurableObject(ns) => call(ns, "contains", ids).await,
+        }
+    }
+}
+
+async fn call(ns: &ObjectNamespace, op: &str, ids: &[u64]) -> Result<Vec<u64>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let stub = ns.id_from_name(DEDUP_OBJECT_NAME)?.get_stub()?;
+    let body = serde_json::to_string(&IdsBody { ids: ids.to_vec() })?;
+    let mut init = RequestInit::new();
+    init.with_method(Method::Post)
+        .with_body(Some(wasm_bindgen::JsValue::from_str(&body)));
+    let req = Request::new_with_init(&format!("https://dedup/{}", op), &init)?;
+    let mut res = stub.fetch_with_request(req).await?;
+    if !(200..300).contains(&res.status_code()) {
+        console_error!("[Dedup] ❌ {} fails with status:{}", op, res.status_code());
+        return Err(Error::RustError(format!(
+            "Error message: dedup index {} failed with status:{}",
+            op,
+            res.status_code()
+        )));
+    }
+    Ok(res.json::<IdsBody>().await?.ids)
+}
@@ -0,0 +1,401 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+use worker::{async_trait::async_trait, *};
+
+use crate::api::hn::HackerNewsItem;
+
+pub const HISTORY_BINDING: &str = "HN_HISTORY";
+
+// Plain SQLite, so the same statements run against a local database
+// (`wrangler d1 execute --local` or the sqlite3 CLI in the tests below)
+const INSERT_SNAPSHOT: &str = "INSERT OR IGNORE INTO item_snapshots \
+    (item_id, fetched_at, title, url, domain, score, descendants) \
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+const TOP_PER_WEEK: &str = "SELECT week, item_id, title, url, max_score FROM ( \
+    SELECT strftime('%Y-%W', fetched_at, 'unixepoch') AS week, item_id, title, url, \
+        MAX(score) AS max_score, \
+        ROW_NUMBER() OVER ( \
+            PARTITION BY strftime('%Y-%W', fetched_at, 'unixepoch') \
+            ORDER BY MAX(score) DESC) AS rank \
+    FROM item_snapshots WHERE fetched_at >= ?1 \
+    GROUP BY week, item_id) \
+    WHERE rank <= ?2 ORDER BY week DESC, max_score DESC";
+const SCORE_TRAJECTORY: &str = "SELECT fetched_at, score, descendants FROM item_snapshots \
+    WHERE item_id = ?1 ORDER BY fetched_at";
+const TOP_DOMAINS: &str = "SELECT domain, COUNT(DISTINCT item_id) AS items FROM item_snapshots \
+    WHERE fetched_at >= ?1 AND domain IS NOT NULL \
+    GROUP BY domain ORDER BY items DESC LIMIT ?2";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub item_id: u64,
+    /// Unix seconds
+    pub fetched_at: u64,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub domain: Option<String>,
+    pub score: Option<u64>,
+    pub descendants: Option<u64>,
+}
+
+impl Snapshot {
+    pub fn new(item: &HackerNewsItem, fetched_at: u64) -> Self {
+        Self {
+            item_id: item.item_id,
+            fetched_at,
+            title: item.title.clone(),
+            url: item.url.clone(),
+            domain: item.url.as_deref().and_then(domain_of),
+            score: item.score,
+            descendants: item.decendants,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WeeklyTop {
+    pub week: String,
+    pub item_id: u64,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub max_score: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrajectoryPoint {
+    pub fetched_at: u64,
+    pub score: Option<u64>,
+    pub descendants: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DomainCount {
+    pub domain: String,
+    pub items: u64,
+}
+
+/// Value bound to a statement parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Int(u64),
+    Text(String),
+}
+
+impl From<u64> for SqlValue {
+    fn from(n: u64) -> Self {
+        Self::Int(n)
+    }
+}
+
+impl From<u32> for SqlValue {
+    fn from(n: u32) -> Self {
+        Self::Int(n.into())
+    }
+}
+
+impl<T: Into<SqlValue>> From<Option<T>> for SqlValue {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Self::Null, Into::into)
+    }
+}
+
+impl From<String> for SqlValue {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+/// SQL backend of `HistoryStore`, D1 in the worker and a local SQLite
+/// database in tests.
+#[async_trait(?Send)]
+pub trait SqlExecutor {
+    /// Run every statement in one transaction.
+    async fn batch(&self, statements: Vec<(&'static str, Vec<SqlValue>)>) -> Result<()>;
+    async fn query(&self, sql: &str, params: Vec<SqlValue>) -> Result<Vec<serde_json::Value>>;
+}
+
+// D1 binds JS numbers; a u64 would become a BigInt
+fn to_js(v: SqlValue) -> JsValue {
+    match v {
+        SqlValue::Null => JsValue::NULL,
+        SqlValue::Int(n) => JsValue::from(n as f64),
+        SqlValue::Text(s) => JsValue::from(s),
+    }
+}
+
+#[async_trait(?Send)]
+impl SqlExecutor for D1Database {
+    async fn batch(&self, statements: Vec<(&'static str, Vec<SqlValue>)>) -> Result<()> {
+        let mut prepared = Vec::with_capacity(statements.len());
+        for (sql, params) in statements {
+            let params = params.into_iter().map(to_js).collect::<Vec<_>>();
+            prepared.push(self.prepare(sql).bind(&params)?);
+        }
+        D1Database::batch(self, prepared).await?;
+        Ok(())
+    }
+
+    async fn query(&self, sql: &str, params: Vec<SqlValue>) -> Result<Vec<serde_json::Value>> {
+        let params = params.into_iter().map(to_js).collect::<Vec<_>>();
+        self.prepare(sql).bind(&params)?.all().await?.results()
+    }
+}
+
+/// Optional D1 sink keeping every fetched snapshot beyond the KV TTL.
+/// Apply `migrations/` with `wrangler d1 migrations apply`.
+pub struct HistoryStore<E = D1Database> {
+    db: E,
+}
+
+impl HistoryStore {
+    /// `None` unless the `HN_HISTORY` D1 binding is configured.
+    pub fn from_env(env: &Env) -> Option<Self> {
+        env.d1(HISTORY_BINDING).ok().map(Self::new)
+    }
+}
+
+impl<E: SqlExecutor> HistoryStore<E> {
+    pub fn new(db: E) -> Self {
+        Self { db }
+    }
+
+    pub async fn record(&self, snapshots: &[Snapshot]) -> Result<()> {
+        if snapshots.is_empty() {
+            return Ok(());
+        }
+        let statements = snapshots
+            .iter()
+            .map(|s| {
+                let params = vec![
+                    s.item_id.into(),
+                    s.fetched_at.into(),
+                    s.title.clone().into(),
+                    s.url.clone().into(),
+                    s.domain.clone().into(),
+                    s.score.into(),
+                    s.descendants.into(),
+                ];
+                (INSERT_SNAPSHOT, params)
+            })
+            .collect();
+        self.db.batch(statements).await?;
+        Ok(())
+    }
+
+    /// Best `per_week` items of every week since `since` (unix seconds).
+    pub async fn top_stories_per_week(&self, since: u64, per_week: u32) -> Result<Vec<WeeklyTop>> {
+        self.rows(TOP_PER_WEEK, vec![since.into(), per_week.into()])
+            .await
+    }
+
+    pub async fn score_trajectory(&self, item_id: u64) -> Result<Vec<TrajectoryPoint>> {
+        self.rows(SCORE_TRAJECTORY, vec![item_id.into()]).await
+    }
+
+    pub async fn top_domains(&self, since: u64, limit: u32) -> Result<Vec<DomainCount>> {
+        self.rows(TOP_DOMAINS, vec![since.into(), limit.into()])
+            .await
+    }
+
+    async fn rows<T: DeserializeOwned>(&self, sql: &str, params: Vec<SqlValue>) -> Result<Vec<T>> {
+        self.db
+            .query(sql, params)
+            .await?
+            .into_iter()
+            .map(|row| serde_json::from_value(row).map_err(Error::from))
+            .collect()
+    }
+}
+
+fn domain_of(url: &str) -> Option<String> {
+    let u = Url::parse(url).ok()?;
+    let host = u.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    use super::*;
+
+    const MIGRATION: &str = include_str!("../migrations/0001_create_item_snapshots.sql");
+    // 2025-10-06, a Monday
+    const WEEK_START: u64 = 1_759_708_800;
+    const DAY: u64 = 86400;
+
+    /// Local SQLite stand-in for D1 through the sqlite3 CLI, parameters are
+    /// inlined as literals.
+    struct Sqlite3Cli {
+        path: PathBuf,
+    }
+
+    impl Sqlite3Cli {
+        fn new(name: &str) -> Self {
+            if Command::new("sqlite3").arg("--version").output().is_err() {
+                panic!("sqlite3 not found on PATH, it is needed for the history tests");
+            }
+            let path =
+                std::env::temp_dir().join(format!("hn-history-{}-{}.db", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let db = Self { path };
+            db.run(&[], MIGRATION);
+            db
+        }
+
+        fn run(&self, args: &[&str], sql: &str) -> String {
+            use std::io::Write;
+            let mut child = Command::new("sqlite3")
+                .args(args)
+                .arg(&self.path)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap();
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(sql.as_bytes())
+                .unwrap();
+            let out = child.wait_with_output().unwrap();
+            assert!(
+                out.status.success(),
+                "{}",
+                String::from_utf8_lossy(&out.stderr)
+            );
+            String::from_utf8(out.stdout).unwrap()
+        }
+    }
+
+    impl Drop for Sqlite3Cli {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn inline(sql: &str, params: &[SqlValue]) -> String {
+        let mut sql = sql.to_string();
+        // Highest index first so `?1` does not match the start of `?10`
+        for (i, v) in params.iter().enumerate().rev() {
+            let literal = match v {
+                SqlValue::Null => "NULL".to_string(),
+                SqlValue::Int(n) => n.to_string(),
+                SqlValue::Text(s) => format!("'{}'", s.replace('\'', "''")),
+            };
+            sql = sql.replace(&format!("?{}", i + 1), &literal);
+        }
+        sql
+    }
+
+    #[async_trait(?Send)]
+    impl SqlExecutor for Sqlite3Cli {
+        async fn batch(&self, statements: Vec<(&'static str, Vec<SqlValue>)>) -> Result<()> {
+            let mut script = "BEGIN;\n".to_string();
+            for (sql, params) in statements {
+                script.push_str(&inline(sql, &params));
+                script.push_str(";\n");
+            }
+            script.push_str("COMMIT;\n");
+            self.run(&[], &script);
+            Ok(())
+        }
+
+        async fn query(&self, sql: &str, params: Vec<SqlValue>) -> Result<Vec<serde_json::Value>> {
+            let out = self.run(&["-json"], &format!("{};", inline(sql, &params)));
+            // No rows prints nothing rather than `[]`
+            if out.trim().is_empty() {
+                return Ok(Vec::new());
+            }
+            Ok(serde_json::from_str(&out)?)
+        }
+    }
+
+    fn snapshot(id: u64, at: u64, url: Option<&str>, score: u64) -> Snapshot {
+        Snapshot {
+            item_id: id,
+            fetched_at: at,
+            title: Some(format!("Story {}", id)),
+            url: url.map(str::to_string),
+            domain: url.and_then(domain_of),
+            score: Some(score),
+            descendants: Some(score / 10),
+        }
+    }
+
+    fn seeded(name: &str) -> HistoryStore<Sqlite3Cli> {
+        let store = HistoryStore::new(Sqlite3Cli::new(name));
+        let week2 = WEEK_START + 7 * DAY;
+        let snapshots = [
+            // Week 1: item 1 peaks at 300, 2 at 200, 3 at 100
+            snapshot(1, WEEK_START, Some("https://www.example.com/a"), 50),
+            snapshot(1, WEEK_START + 600, Some("https://www.example.com/a"), 300),
+            snapshot(
+                2,
+                WEEK_START + DAY,
+                Some("https://blog.rust-lang.org/x"),
+                200,
+            ),
+            snapshot(3, WEEK_START + DAY, None, 100),
+            // Week 2: item 4 at 500, 5 at 400, all from example.com
+            snapshot(4, week2, Some("https://example.com/b"), 500),
+            snapshot(5, week2 + DAY, Some("https://example.com/c"), 400),
+        ];
+        futures_executor::block_on(store.record(&snapshots)).unwrap();
+        // Same key again is ignored
+        futures_executor::block_on(store.record(&snapshots[..1])).unwrap();
+        store
+    }
+
+    #[test]
+    fn top_per_week_ranks_by_peak_score() {
+        let store = seeded("weekly");
+        let rows = futures_executor::block_on(store.top_stories_per_week(WEEK_START, 2)).unwrap();
+        let got = rows
+            .iter()
+            .map(|r| (r.week.as_str(), r.item_id, r.max_score))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            got,
+            [
+                ("2025-41", 4, Some(500)),
+                ("2025-41", 5, Some(400)),
+                ("2025-40", 1, Some(300)),
+                ("2025-40", 2, Some(200)),
+            ]
+        );
+        let rows = futures_executor::block_on(store.top_stories_per_week(WEEK_START + 7 * DAY, 10))
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn top_domains_count_distinct_items() {
+        let store = seeded("domains");
+        let rows = futures_executor::block_on(store.top_domains(WEEK_START, 10)).unwrap();
+        let got = rows
+            .iter()
+            .map(|r| (r.domain.as_str(), r.items))
+            .collect::<Vec<_>>();
+        // `www.` is folded, items without a url are skipped
+        assert_eq!(got, [("example.com", 3), ("blog.rust-lang.org", 1)]);
+        let rows = futures_executor::block_on(store.top_domains(WEEK_START, 1)).unwrap();
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[test]
+    fn score_trajectory_is_ordered() {
+        let store = seeded("trajectory");
+        let rows = futures_executor::block_on(store.score_trajectory(1)).unwrap();
+        let got = rows.iter().map(|r| r.score).collect::<Vec<_>>();
+        assert_eq!(got, [Some(50), Some(300)]);
+        assert!(futures_executor::block_on(store.score_trajectory(42))
+            .unwrap()
+            .is_empty());
+    }
+}
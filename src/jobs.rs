@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::{
+    config::{ConfigError, RUN_LOG_MAX},
+    keys::RUNS_PREFIX,
+    lock::{acquire, current_lease, release, Lease},
+    scheduled::{run_telegram_job, JobReport},
+    state::AppState,
+};
+
+// Triggers are read from the deployed manifest so the registry cannot drift
+// from `triggers.crons` unnoticed
+const WRANGLER_TOML: &str = include_str!("../wrangler.toml");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Telegram,
+    Email,
+}
+
+/// Per-job knobs, on top of the shared `Config`.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct JobConfig {
+    pub shards: Option<u16>,
+    /// The job is reported stale when its last run is older than this
+    pub max_age_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct JobSpec {
+    pub cron: &'static str,
+    pub name: &'static str,
+    pub kind: JobKind,
+    pub config: JobConfig,
+}
+
+/// Every cron handled by the worker. Add a row here when adding a trigger.
+pub const JOBS: &[JobSpec] = &[
+    JobSpec {
+        cron: "*/10 * * * *",
+        name: "telegram",
+        kind: JobKind::Telegram,
+        config: JobConfig {
+            shards: None,
+            max_age_secs: 30 * 60,
+        },
+    },
+    JobSpec {
+        cron: "30 9 * * mon,wed,fri",
+        name: "email",
+        kind: JobKind::Email,
+        config: JobConfig {
+            shards: None,
+            max_age_secs: 4 * 86400,
+        },
+    },
+];
+
+#[derive(Debug)]
+pub enum JobError {
+    UnknownCron {
+        cron: String,
+        known: Vec<&'static str>,
+    },
+    UnknownJob {
+        name: String,
+        known: Vec<&'static str>,
+    },
+    /// Triggers in `wrangler.toml` without a row in `JOBS`
+    MissingHandlers {
+        crons: Vec<String>,
+        known: Vec<&'static str>,
+    },
+    NotImplemented(&'static str),
+    /// Another run holds the job lease
+    Locked(Lease),
+    /// Env vars or bindings are invalid, jobs do not run on defaults
+    Config(ConfigError),
+    Run(Error),
+}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobError::UnknownCron { cron, known } => write!(
+                f,
+                "Error message: no job for cron {:?}. Known crons: {:?}",
+                cron, known
+            ),
+            JobError::UnknownJob { name, known } => write!(
+                f,
+                "Error message: no job named {:?}. Known jobs: {:?}",
+                name, known
+            ),
+            JobError::MissingHandlers { crons, known } => write!(
+                f,
+                "Error message: triggers {:?} have no job. Known crons: {:?}",
+                crons, known
+            ),
+            JobError::NotImplemented(name) => {
+                write!(f, "Error message: job {} is not implemented", name)
+            }
+            JobError::Locked(lease) => write!(
+                f,
+                "Error message: job {} is running, lease owner:{} expires at:{}",
+                lease.job, lease.owner, lease.expires_at
+            ),
+            JobError::Config(e) => write!(f, "{}", e),
+            JobError::Run(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+impl From<Error> for JobError {
+    fn from(e: Error) -> Self {
+        JobError::Run(e)
+    }
+}
+
+impl From<JobError> for Error {
+    fn from(e: JobError) -> Self {
+        match e {
+            JobError::Run(e) => e,
+            e => Error::RustError(e.to_string()),
+        }
+    }
+}
+
+pub fn known_crons() -> Vec<&'static str> {
+    JOBS.iter().map(|j| j.cron).collect()
+}
+
+pub fn job_for_cron(cron: &str) -> Result<&'static JobSpec, JobError> {
+    JOBS.iter()
+        .find(|j| j.cron == cron)
+        .ok_or_else(|| JobError::UnknownCron {
+            cron: cron.to_string(),
+            known: known_crons(),
+        })
+}
+
+pub fn job_by_name(name: &str) -> Result<&'static JobSpec, JobError> {
+    JOBS.iter()
+        .find(|j| j.name == name)
+        .ok_or_else(|| JobError::UnknownJob {
+            name: name.to_string(),
+            known: JOBS.iter().map(|j| j.name).collect(),
+        })
+}
+
+/// `triggers.crons` of the bundled `wrangler.toml`, every environment included.
+pub fn configured_crons() -> Vec<String> {
+    parse_crons(WRANGLER_TOML)
+}
+
+// Just enough TOML for `crons` under `[triggers]` or `[env.<name>.triggers]`,
+// also as a dotted key, with arrays that may span lines
+fn parse_crons(toml: &str) -> Vec<String> {
+    let mut crons = Vec::new();
+    let mut table = String::new();
+    let mut in_array = false;
+    for line in toml.lines() {
+        let line = strip_comment(line).trim();
+        if in_array {
+            in_array = !collect_quoted(line, &mut crons);
+            continue;
+        }
+        if line.starts_with('[') {
+            table = line.trim_matches(['[', ']']).trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let path = match table.as_str() {
+            "" => key.trim().to_string(),
+            t => format!("{}.{}", t, key.trim()),
+        };
+        if is_crons_path(&path) {
+            in_array = !collect_quoted(value, &mut crons);
+        }
+    }
+    crons
+}
+
+fn is_crons_path(path: &str) -> bool {
+    let parts = path.split('.').map(str::trim).collect::<Vec<_>>();
+    matches!(
+        parts.as_slice(),
+        ["triggers", "crons"] | ["env", _, "triggers", "crons"]
+    )
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+// Push the quoted strings of `s` not seen yet, true once the array is closed
+fn collect_quoted(s: &str, out: &mut Vec<String>) -> bool {
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        match (start, c) {
+            (None, '"' | '\'') => start = Some((i + 1, c)),
+            (Some((from, q)), c) if c == q => {
+                if !out.iter().any(|o| o == &s[from..i]) {
+                    out.push(s[from..i].to_string());
+                }
+                start = None;
+            }
+            (None, ']') => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Every configured trigger must have a job.
+pub fn validate_triggers() -> Result<(), JobError> {
+    let missing = configured_crons()
+        .into_iter()
+        .filter(|c| job_for_cron(c).is_err())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(JobError::MissingHandlers {
+            crons: missing,
+            known: known_crons(),
+        });
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub job: String,
+    /// Unix millis
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    pub fetched: usize,
+    pub filtered: usize,
+    pub notified: usize,
+    pub failed: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Health {
+    /// No run recorded yet
+    Unknown,
+    Ok,
+    /// The last run failed
+    Degraded,
+    /// No run within `max_age_secs`
+    Stale,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobStatus {
+    pub job: &'static str,
+    pub cron: &'static str,
+    pub health: Health,
+    /// Lease of a run in progress
+    pub lock: Option<Lease>,
+    pub runs: Vec<RunRecord>,
+}
+
+fn run_log_key(job: &str) -> String {
+    format!("{}{}", RUNS_PREFIX, job)
+}
+
+/// Newest first, at most `RUN_LOG_MAX` records per job.
+pub async fn load_runs(kv: &KvStore, job: &str) -> Result<Vec<RunRecord>> {
+    Ok(kv
+        .get(&run_log_key(job))
+        .json::<Vec<RunRecord>>()
+        .await?
+        .unwrap_or_default())
+}
+
+async fn record_run(kv: &KvStore, record: RunRecord) -> Result<()> {
+    let mut runs = load_runs(kv, &record.job).await?;
+    runs.insert(0, record);
+    runs.truncate(RUN_LOG_MAX);
+    kv.put(&run_log_key(&runs[0].job), serde_json::to_string(&runs)?)?
+        .execute()
+        .await?;
+    Ok(())
+}
+
+pub fn health(spec: &JobSpec, runs: &[RunRecord], now_ms: u64) -> Health {
+    let Some(last) = runs.first() else {
+        return Health::Unknown;
+    };
+    if now_ms.saturating_sub(last.finished_at) > spec.config.max_age_secs * 1000 {
+        Health::Stale
+    } else if last.error.is_some() {
+        Health::Degraded
+    } else {
+        Health::Ok
+    }
+}
+
+pub async fn job_statuses(kv: &KvStore, limit: usize) -> Result<Vec<JobStatus>> {
+    let now = Date::now().as_millis();
+    let mut out = Vec::with_capacity(JOBS.len());
+    for spec in JOBS {
+        let mut runs = load_runs(kv, spec.name).await?;
+        let health = health(spec, &runs, now);
+        let lock = current_lease(kv, spec.name)
+            .await?
+            .filter(|l| !l.is_expired(now));
+        runs.truncate(limit);
+        out.push(JobStatus {
+            job: spec.name,
+            cron: spec.cron,
+            health,
+            lock,
+            runs,
+        });
+    }
+    Ok(out)
+}
+
+/// Run a job under its lease and append it to the run log. Dry runs take no
+/// lease and, like jobs that are not implemented, leave no record.
+pub async fn run_job(
+    state: &AppState,
+    spec: &JobSpec,
+    dry_run: bool,
+) -> Result<JobReport, JobError> {
+    console_log!("[Jobs] Run job:{} cron:{}", spec.name, spec.cron);
+    state.checked_config().map_err(JobError::Config)?;
+    if spec.kind == JobKind::Email {
+        // TODO scheduled::run_email_job(env).await
+        return Err(JobError::NotImplemented(spec.name));
+    }
+    if dry_run {
+        return Ok(run_telegram_job(state, spec.config.shards, true).await?);
+    }
+    let kv = state.kvm.kv();
+    let lease = acquire(kv, spec.name).await?.map_err(JobError::Locked)?;
+    let started_at = Date::now().as_millis();
+    let result = run_telegram_job(state, spec.config.shards, false).await;
+    if let Err(e) = release(kv, &lease).await {
+        console_warn!(
+            "[Jobs] ⚠️ Release lease of job:{} fails. Error: {}",
+            spec.name,
+            e
+        );
+    }
+    let finished_at = Date::now().as_millis();
+    let mut record = RunRecord {
+        job: spec.name.to_string(),
+        started_at,
+        finished_at,
+        duration_ms: finished_at - started_at,
+        ..Default::default()
+    };
+    let result = result.and_then(|report| {
+        record.fetched = report.fetched;
+        record.filtered = report.filtered.len();
+        record.notified = report.notified;
+        record.failed = report.failed.len();
+        report.into_result()
+    });
+    if let Err(e) = result.as_ref() {
+        record.error = Some(e.to_string());
+    }
+    if let Err(e) = record_run(kv, record).await {
+        console_warn!(
+            "[Jobs] ⚠️ Record run of job:{} fails. Error: {}",
+            spec.name,
+            e
+        );
+    }
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_triggers_have_jobs() {
+        assert!(validate_triggers().is_ok(), "{:?}", validate_triggers());
+    }
+
+    #[test]
+    fn parses_inline_crons() {
+        let toml = "[triggers]\ncrons = [\"*/10 * * * *\", '30 9 * * mon,wed,fri'] # prod\n";
+        assert_eq!(
+            parse_crons(toml),
+            vec!["*/10 * * * *", "30 9 * * mon,wed,fri"]
+        );
+    }
+
+    #[test]
+    fn parses_multi_line_crons() {
+        let toml = r#"
+name = "worker"
+
+[triggers]
+crons = [
+    "*/10 * * * *", # telegram
+    # "0 0 * * *",
+    "30 9 * * mon,wed,fri",
+]
+
+[vars]
+crons = "not a trigger"
+"#;
+        assert_eq!(
+            parse_crons(toml),
+            vec!["*/10 * * * *", "30 9 * * mon,wed,fri"]
+        );
+    }
+
+    #[test]
+    fn parses_env_and_dotted_triggers() {
+        let toml = r#"
+triggers.crons = ["*/10 * * * *"]
+
+[env.staging.triggers]
+crons = [
+  "*/30 * * * *",
+  "*/10 * * * *",
+]
+
+[env.staging.vars]
+LINK_BASE = "https://example.com"
+"#;
+        assert_eq!(parse_crons(toml), vec!["*/10 * * * *", "*/30 * * * *"]);
+    }
+
+    #[test]
+    fn missing_handlers_are_listed() {
+        let unknown = parse_crons("[env.dev.triggers]\ncrons = [\"0 0 * * *\"]")
+            .into_iter()
+            .filter(|c| job_for_cron(c).is_err())
+            .collect::<Vec<_>>();
+        assert_eq!(unknown, vec!["0 0 * * *"]);
+    }
+}
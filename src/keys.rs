@@ -0,0 +1,57 @@
+// KV keys kept next to the cached items. Items live under `KV_PREFIX` (`HN-`
+// by default) and are found by listing that prefix, so every key here must sit
+// outside it; `Config::from_env` reports a `KV_PREFIX` that overlaps one and
+// keeps the default.
+pub const KV_TTL_KEY: &str = "TTL";
+pub const SETTINGS_KEY: &str = "SETTINGS";
+pub const TRAJECTORY_KEY: &str = "TRAJECTORY";
+pub const ARTICLE_PREFIX: &str = "ARTICLE-";
+pub const DELIVERY_PREFIX: &str = "DELIVERY-";
+pub const CLICKS_PREFIX: &str = "CLICKS-";
+pub const LOCK_PREFIX: &str = "LOCK-";
+pub const RUNS_PREFIX: &str = "RUNS-";
+pub const SCORE_PREFIX: &str = "SCORE-";
+
+const RESERVED: &[&str] = &[
+    KV_TTL_KEY,
+    SETTINGS_KEY,
+    TRAJECTORY_KEY,
+    ARTICLE_PREFIX,
+    DELIVERY_PREFIX,
+    CLICKS_PREFIX,
+    LOCK_PREFIX,
+    RUNS_PREFIX,
+    SCORE_PREFIX,
+];
+
+/// The reserved key or prefix an item prefix would overlap, if any: listing
+/// the prefix would return it, or item keys would land in its namespace.
+pub fn reserved_overlap(item_prefix: &str) -> Option<&'static str> {
+    RESERVED
+        .iter()
+        .copied()
+        .find(|k| k.starts_with(item_prefix) || (k.ends_with('-') && item_prefix.starts_with(k)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KV_PREFIX_DEFAULT;
+
+    #[test]
+    fn default_prefix_is_clear() {
+        assert_eq!(reserved_overlap(KV_PREFIX_DEFAULT), None);
+        assert_eq!(reserved_overlap("HN"), None);
+        assert_eq!(reserved_overlap("TTL-"), None);
+    }
+
+    #[test]
+    fn overlapping_prefixes_are_found() {
+        assert_eq!(reserved_overlap(""), Some(KV_TTL_KEY));
+        assert_eq!(reserved_overlap("T"), Some(KV_TTL_KEY));
+        assert_eq!(reserved_overlap("SETTINGS"), Some(SETTINGS_KEY));
+        assert_eq!(reserved_overlap("ARTICLE-"), Some(ARTICLE_PREFIX));
+        assert_eq!(reserved_overlap("LOCK-tele"), Some(LOCK_PREFIX));
+        assert_eq!(reserved_overlap("R"), Some(RUNS_PREFIX));
+    }
+}
@@ -1,12 +1,122 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::fmt::Debug;
 use worker::*;
 
+use crate::{
+    config::{DELIVERY_TTL, KV_META_MAX_BYTES, MAX_DELIVERY_ATTEMPTS},
+    keys::DELIVERY_PREFIX,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    /// Cached but not yet delivered to every sink
+    Pending,
+    Delivered,
+    /// Gave up after `MAX_DELIVERY_ATTEMPTS`
+    Failed,
+}
+
+fn lenient_score<'de, D>(deserializer: D) -> std::result::Result<Option<f32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let v = serde_json::Value::deserialize(deserializer)?;
+    Ok(v.as_f64().map(|s| s as f32))
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeliveryReceipt {
+    pub channel: String,
+    /// Remote message/post reference (Telegram message id, at:// uri, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Unix seconds
+    pub timestamp: u64,
+}
+
+/// Delivery progress of one item, kept in its own `DELIVERY-<id>` value
+/// rather than in item metadata so receipts never compete for the 1024 byte
+/// metadata budget. State and attempts are mirrored in `DeliveryMeta`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    state: DeliveryState,
+    #[serde(default)]
+    attempts: u8,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    receipts: Vec<DeliveryReceipt>,
+}
+
+impl Default for DeliveryRecord {
+    fn default() -> Self {
+        Self {
+            state: DeliveryState::Pending,
+            attempts: 0,
+            receipts: Vec::new(),
+        }
+    }
+}
+
+impl DeliveryRecord {
+    pub fn with_state(&mut self, state: DeliveryState) -> &mut Self {
+        self.state = state;
+        self
+    }
+    pub fn with_receipt(&mut self, receipt: DeliveryReceipt) -> &mut Self {
+        self.receipts.retain(|r| r.channel != receipt.channel);
+        self.receipts.push(receipt);
+        self
+    }
+    pub fn add_attempt(&mut self) -> &mut Self {
+        self.attempts = self.attempts.saturating_add(1);
+        self
+    }
+    pub fn state(&self) -> DeliveryState {
+        self.state
+    }
+    pub fn attempts(&self) -> u8 {
+        self.attempts
+    }
+    pub fn receipts(&self) -> &[DeliveryReceipt] {
+        &self.receipts
+    }
+    pub fn is_delivered_to(&self, channel: &str) -> bool {
+        self.receipts.iter().any(|r| r.channel == channel)
+    }
+    pub fn meta(&self) -> DeliveryMeta {
+        DeliveryMeta {
+            state: self.state,
+            attempts: self.attempts,
+        }
+    }
+}
+
+/// Metadata of a `DELIVERY-<id>` key, so one list finds every pending item
+/// without reading the records.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct DeliveryMeta {
+    pub state: DeliveryState,
+    #[serde(default)]
+    pub attempts: u8,
+}
+
+impl DeliveryMeta {
+    /// Pending and not yet given up on.
+    pub fn is_retryable(&self) -> bool {
+        self.state == DeliveryState::Pending && self.attempts < MAX_DELIVERY_ATTEMPTS
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KVMeta {
     uuid: String,
     llm_summary: Option<String>,
-    llm_score: Option<String>,
+    // Entries from before numeric scores hold a placeholder string, read them as unscored
+    #[serde(default, deserialize_with = "lenient_score")]
+    llm_score: Option<f32>,
+    // Short description of the velocity that triggered an early alert
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rising: Option<String>,
 }
 
 impl KVMeta {
@@ -15,16 +125,39 @@ impl KVMeta {
             uuid: uuid.into(),
             llm_summary: None,
             llm_score: None,
+            rising: None,
         }
     }
     pub fn with_llm_summary(&mut self, llm_summary: Option<String>) -> &mut Self {
         self.llm_summary = llm_summary;
         self
     }
-    pub fn with_llm_score(&mut self, llm_score: Option<String>) -> &mut Self {
+    pub fn with_llm_score(&mut self, llm_score: Option<f32>) -> &mut Self {
         self.llm_score = llm_score;
         self
     }
+    pub fn with_rising(&mut self, rising: Option<String>) -> &mut Self {
+        self.rising = rising;
+        self
+    }
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+    pub fn llm_summary(&self) -> Option<&str> {
+        self.llm_summary.as_deref()
+    }
+    pub fn llm_score(&self) -> Option<f32> {
+        self.llm_score
+    }
+    pub fn rising(&self) -> Option<&str> {
+        self.rising.as_deref()
+    }
+}
+
+fn fits_meta_limit<T: Serialize>(meta: &T) -> bool {
+    serde_json::to_string(meta)
+        .map(|m| m.len() <= KV_META_MAX_BYTES)
+        .unwrap_or(false)
 }
 
 #[derive(Clone, Debug)]
@@ -36,7 +169,8 @@ pub struct KVManager {
 }
 
 impl KVManager {
-    fn new(kv: KvStore, prefix: String, ttl_key: String, ttl_val: u64) -> Self {
+    /// Manager without the TTL bookkeeping write of `init`, for read-only use.
+    pub fn new(kv: KvStore, prefix: String, ttl_key: String, ttl_val: u64) -> Self {
         Self {
             kv,
             prefix,
@@ -55,54 +189,121 @@ impl KVManager {
         let k = ttl_key.into();
         let v = ttl_val;
         let mgr = KVManager::new(kv, p, k, v);
-        match mgr.kv.get(&mgr.ttl_key).text().await? {
+        mgr.sync_ttl().await?;
+        Ok(mgr)
+    }
+
+    /// Record the TTL in use under `ttl_key`, as `init` does.
+    pub async fn sync_ttl(&self) -> Result<()> {
+        match self.kv.get(&self.ttl_key).text().await? {
             None => {
                 console_log!(
                     "[KVManager] Init key key:{} value:{} in KVManager init",
-                    mgr.ttl_key,
-                    mgr.ttl_val
+                    self.ttl_key,
+                    self.ttl_val
                 );
-                mgr.kv
-                    .put(&mgr.ttl_key, mgr.ttl_val.to_string())?
-                    .execute()
-                    .await?;
-                Ok(mgr)
             }
             Some(cur) => {
                 console_log!(
                     "[KVManager] ⚠️ Rewrite key:{} value:{}->{} in KVManager init",
-                    mgr.ttl_key,
+                    self.ttl_key,
                     cur,
-                    mgr.ttl_val
+                    self.ttl_val
                 );
-                mgr.kv
-                    .put(&mgr.ttl_key, mgr.ttl_val.to_string())?
-                    .execute()
-                    .await?;
-                Ok(mgr)
             }
         }
+        self.kv
+            .put(&self.ttl_key, self.ttl_val.to_string())?
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    pub fn kv(&self) -> &KvStore {
+        &self.kv
+    }
+
+    pub fn item_key(&self, id: u64) -> String {
+        format!("{}{}", self.prefix, id)
+    }
+
+    pub fn delivery_key(&self, id: u64) -> String {
+        format!("{}{}", DELIVERY_PREFIX, id)
     }
 
-    // TODO
-    // pub async fn list_keys_meta(
-    //   &self,
-    //   prefix: Option<&str>,
-    //   if_once: Option<bool>,
-    // ) -> Result< >{}
+    /// `None` for items cached before delivery records existed, those were
+    /// notified right after caching.
+    pub async fn get_delivery(&self, id: u64) -> Result<Option<DeliveryRecord>> {
+        self.get_json(self.delivery_key(id)).await
+    }
+
+    /// Outlives the item cache so post references stay around for deletion.
+    pub async fn put_delivery(&self, id: u64, record: &DeliveryRecord) -> Result<()> {
+        self.kv
+            .put(&self.delivery_key(id), serde_json::to_string(record)?)?
+            .metadata(record.meta())?
+            .expiration_ttl(DELIVERY_TTL.max(self.ttl_val))
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    /// Ids of delivery records left pending by earlier runs, from one list of
+    /// the `DELIVERY-` keys.
+    pub async fn pending_deliveries(&self) -> Result<Vec<u64>> {
+        let keys = self
+            .list_keys_meta::<DeliveryMeta>(Some(DELIVERY_PREFIX), false)
+            .await?;
+        Ok(keys
+            .into_iter()
+            .filter(|(_, meta)| meta.is_some_and(|m| m.is_retryable()))
+            .filter_map(|(key, _)| key.strip_prefix(DELIVERY_PREFIX)?.parse().ok())
+            .collect())
+    }
+
+    /// List keys with their parsed metadata. Follows the cursor unless `if_once`.
+    pub async fn list_keys_meta<T: DeserializeOwned>(
+        &self,
+        prefix: Option<&str>,
+        if_once: bool,
+    ) -> Result<Vec<(String, Option<T>)>> {
+        let prefix = prefix.unwrap_or(&self.prefix);
+        let mut out = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut builder = KvStore::list(&self.kv).prefix(prefix.into());
+            if let Some(c) = cursor.take() {
+                builder = builder.cursor(c);
+            }
+            let res = builder.execute().await?;
+            out.extend(res.keys.into_iter().map(|k| {
+                let meta = k.metadata.and_then(|m| serde_json::from_value::<T>(m).ok());
+                (k.name, meta)
+            }));
+            match res.cursor {
+                Some(c) if !if_once && !res.list_complete => cursor = Some(c),
+                _ => {
+                    if if_once && !res.list_complete {
+                        console_warn!("[KVManager] ⚠️ List once keys meta with prefix:{} overflow limit, some keys may missing.", prefix);
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(out)
+    }
 
     pub async fn list_keys(&self, prefix: Option<&str>, if_once: bool) -> Result<Vec<String>> {
-        let res = if if_once {
+        if if_once {
             self.list_once(prefix).await
         } else {
             self.list_all(prefix, None).await
-        };
-        return res;
+        }
     }
 
     pub async fn list_once(&self, prefix: Option<&str>) -> Result<Vec<String>> {
         let prefix = prefix.unwrap_or(&self.prefix);
-        if !prefix.starts_with("HN") {
+        if !prefix.starts_with(&self.prefix) {
             console_warn!(
                 "[KVManager] ⚠️ Try list once cached keys without proper prefix:{}. Please check.",
                 prefix
@@ -129,20 +330,13 @@ impl KVManager {
 
     pub async fn list_all(
         &self,
-        _prefix: Option<&str>,
+        prefix: Option<&str>,
         _cursor: Option<&str>,
     ) -> Result<Vec<String>> {
-        // TODO
-        Ok(vec![])
+        let keys = self.list_keys_meta::<KVMeta>(prefix, false).await?;
+        Ok(keys.into_iter().map(|(name, _)| name).collect())
     }
 
-    fn check_meta_limit<T>(&self, _meta: &T) -> bool
-    where
-        T: Serialize,
-    {
-        // TODO
-        return true;
-    }
     pub async fn create<T>(
         &self,
         key: impl AsRef<str>,
@@ -160,16 +354,18 @@ impl KVManager {
             .put(k, v)?
             .expiration_ttl(ttl.unwrap_or(self.ttl_val));
         if let Some(ref m) = meta {
-            if !self.check_meta_limit(m) {
-                console_warn!(
-                    "[KVManager] ⚠️ Metadata {:?} too large for key:{}. Please check.",
+            if !fits_meta_limit(m) {
+                console_error!(
+                    "[KVManager] ❌ Metadata {:?} too large for key:{}. Refuse to write.",
                     m,
                     k,
                 );
-                builder = builder.metadata(serde_json::json!({}))?
-            } else {
-                builder = builder.metadata(m)?
+                return Err(Error::RustError(format!(
+                    "Error message: metadata for key:{} exceeds {} bytes",
+                    k, KV_META_MAX_BYTES
+                )));
             }
+            builder = builder.metadata(m)?
         }
         builder.execute().await?;
         Ok(())
@@ -193,7 +389,84 @@ impl KVManager {
     pub async fn delete(&self, key: impl AsRef<str>) -> Result<()> {
         let k = key.as_ref();
         console_warn!("[KVManager] ⚠️ Try delete key:{}. Please check.", k);
-        let _ = self.kv.delete(k).await?;
+        self.kv.delete(k).await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LLM_SUMMARY_MAX_BYTES;
+
+    #[test]
+    fn largest_meta_fits_limit() {
+        let mut meta = KVMeta::new("3f2b8c1e-7a4d-4f7e-9b1a-2c5d6e7f8a9b");
+        // Quotes escape to two bytes each, the trim in `summarize` accounts for that
+        meta.with_llm_summary(Some("\"".repeat(LLM_SUMMARY_MAX_BYTES / 2)))
+            .with_llm_score(Some(9.75))
+            .with_rising(Some("12.5 pts/min, #60 → #1 in 20 min".to_string()));
+        assert!(
+            fits_meta_limit(&meta),
+            "{} bytes",
+            serde_json::to_string(&meta).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn legacy_meta_with_delivery_fields_still_parses() {
+        let legacy = serde_json::json!({
+            "uuid": "u",
+            "llm_summary": null,
+            "llm_score": "placeholder",
+            "state": "pending",
+            "attempts": 1,
+            "receipts": [{"channel": "telegram", "message_id": "7", "timestamp": 1}],
+        });
+        let meta = serde_json::from_value::<KVMeta>(legacy).unwrap();
+        assert_eq!(meta.uuid(), "u");
+        assert_eq!(meta.llm_score(), None);
+    }
+
+    #[test]
+    fn delivery_record_keeps_one_receipt_per_channel() {
+        let mut record = DeliveryRecord::default();
+        for id in ["1", "2"] {
+            record.with_receipt(DeliveryReceipt {
+                channel: "bluesky".to_string(),
+                message_id: Some(format!("at://did:plc:x/app.bsky.feed.post/{}", id)),
+                timestamp: 1,
+            });
+        }
+        record.add_attempt().with_state(DeliveryState::Delivered);
+        let json = serde_json::to_string(&record).unwrap();
+        let record = serde_json::from_str::<DeliveryRecord>(&json).unwrap();
+        assert_eq!(record.state(), DeliveryState::Delivered);
+        assert_eq!(record.attempts(), 1);
+        assert_eq!(record.receipts().len(), 1);
+        assert!(record.is_delivered_to("bluesky"));
+        assert!(!record.is_delivered_to("telegram"));
+    }
+
+    #[test]
+    fn delivery_meta_marks_retryable_records() {
+        let mut record = DeliveryRecord::default();
+        assert!(record.meta().is_retryable());
+        for _ in 0..MAX_DELIVERY_ATTEMPTS {
+            record.add_attempt();
+        }
+        assert!(!record.meta().is_retryable());
+        let json = serde_json::to_value(
+            DeliveryRecord::default()
+                .with_state(DeliveryState::Delivered)
+                .meta(),
+        )
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"state": "delivered", "attempts": 0})
+        );
+        let meta = serde_json::from_value::<DeliveryMeta>(json).unwrap();
+        assert!(!meta.is_retryable());
+    }
+}
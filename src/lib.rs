@@ -1,34 +1,72 @@
 pub mod api;
+pub mod article;
+pub mod auth;
 pub mod config;
+pub mod dedup;
+pub mod history;
+pub mod jobs;
+pub mod keys;
 pub mod kvm;
+pub mod llm;
+pub mod lock;
+pub mod notify;
 pub mod router;
 pub mod scheduled;
+pub mod settings;
+pub mod state;
+pub mod stats;
+pub mod trajectory;
 pub mod utils;
 
-use axum::{routing::get, Router};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
 
+use state::AppState;
 use tower_service::Service;
 
 use worker::*;
 
-fn router() -> Router {
+fn router(state: AppState) -> Router {
     Router::new()
         .route("/", get(router::get_root))
         .route("/about", get(router::get_about))
         .route("/blog", get(router::get_blog))
         .route("/forward/{key}", get(router::get_forward_key))
         .route("/forward/{item}/{id}", get(router::get_forward_item))
+        .route("/s/{code}", get(router::get_short_story))
+        .route("/c/{code}", get(router::get_short_comments))
+        .route("/api/stats/{id}", get(router::get_stats))
+        .route("/api/items/{id}/history", get(router::get_item_history))
+        .route("/status", get(router::get_status))
+        .route("/api/history/top", get(router::get_history_top))
+        .route("/api/history/domains", get(router::get_history_domains))
+        .nest("/admin", admin_router(state.clone()))
         .fallback(router::fallback_handler)
-    // .with_state(state)
+        .with_state(state)
+}
+
+fn admin_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/run/{job}", post(router::post_run_job))
+        .route("/config", get(router::get_config))
+        .route(
+            "/settings",
+            get(router::get_settings).put(router::put_settings),
+        )
+        .route_layer(middleware::from_fn_with_state(state, auth::require_admin))
 }
 
 #[event(fetch)]
 async fn fetch(
     req: HttpRequest,
-    _env: Env,
-    _ctx: Context,
+    env: Env,
+    ctx: Context,
 ) -> Result<axum::http::Response<axum::body::Body>> {
-    Ok(router().call(req).await?)
+    let state = AppState::from_env(env)?.with_context(ctx);
+    Ok(router(state).call(req).await?)
 }
 
 #[event(scheduled)]
@@ -37,18 +75,25 @@ async fn scheduled(event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
         "[Scheduled] Scheduled event triggered at: {}",
         js_sys::Date::new_0().to_iso_string()
     );
-    match event.cron().as_str() {
-        "*/10 * * * *" => {
-            if let Err(e) = scheduled::run_telegram_job(env, None).await {
-                console_error!("[Scheduled] ❌ Catch Error: {}", e);
-            }
+    let spec = match jobs::job_for_cron(&event.cron()) {
+        Ok(spec) => spec,
+        Err(e) => {
+            console_warn!("[Scheduled] ⚠️ {}. https://github.com/edwardzcn-decade/hacker-news-worker/tree/main?tab=readme-ov-file#scheduled-jobs", e);
+            return;
         }
-        "30 9 * * mon,wed,fri" => {
-            // TODO scheduled::run_email_job(env).await
-            console_warn!("[Scheduled] ⚠️ run_email_job not implement. Just skip");
+    };
+    let state = match AppState::from_env(env) {
+        Ok(s) => s,
+        Err(e) => {
+            console_error!("[Scheduled] ❌ Build state fails. Error: {}", e);
+            return;
         }
-        _ => {
-            console_warn!("[Scheduled] ⚠️ Mismatch cron expression: {}. https://github.com/edwardzcn-decade/hacker-news-worker/tree/main?tab=readme-ov-file#scheduled-jobs", event.cron());
+    };
+    match jobs::run_job(&state, spec, false).await {
+        Ok(_) => {}
+        Err(e @ (jobs::JobError::NotImplemented(_) | jobs::JobError::Locked(_))) => {
+            console_warn!("[Scheduled] ⚠️ {}. Just skip", e)
         }
+        Err(e) => console_error!("[Scheduled] ❌ Catch Error: {}", e),
     }
 }
@@ -0,0 +1,172 @@
+pub mod openai;
+pub mod score;
+pub mod workers_ai;
+
+use worker::{async_trait::async_trait, *};
+
+use crate::{
+    api::hn::HackerNewsItem,
+    config::LLM_SUMMARY_MAX_BYTES,
+    utils::tools::env_string,
+    utils::tools::{strip_html, truncate_graphemes},
+};
+
+pub use openai::OpenAiProvider;
+pub use score::{
+    cache_score, cached_score, combined_score, score, threshold_decision, ScoringConfig,
+};
+pub use workers_ai::WorkersAiProvider;
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You summarize Hacker News stories for a chat channel. \
+Answer with at most two plain sentences, no markdown, no preamble.";
+
+#[async_trait(?Send)]
+pub trait LlmProvider {
+    fn name(&self) -> &'static str;
+    async fn complete(&self, system: &str, prompt: &str) -> Result<String>;
+}
+
+/// Offline provider answering a fixed reply, selected with `LLM_PROVIDER=mock`.
+pub struct MockProvider {
+    reply: String,
+}
+
+impl MockProvider {
+    pub fn new(reply: impl Into<String>) -> Self {
+        Self {
+            reply: reply.into(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn complete(&self, _system: &str, _prompt: &str) -> Result<String> {
+        Ok(self.reply.clone())
+    }
+}
+
+/// Pick the provider from `LLM_PROVIDER` (`workers_ai`, `openai` or `mock`).
+/// Unset disables the LLM stage.
+pub fn provider_from_env(env: &Env) -> Option<Box<dyn LlmProvider>> {
+    let provider = env_string(env, "LLM_PROVIDER")?;
+    let model = env_string(env, "LLM_MODEL");
+    match provider.as_str() {
+        "workers_ai" => match WorkersAiProvider::from_env(env, model) {
+            Ok(p) => Some(Box::new(p)),
+            Err(e) => {
+                console_error!("[LLM] ❌ Workers AI binding missing in Env. Error: {}", e);
+                None
+            }
+        },
+        "openai" => OpenAiProvider::from_env(env, model).map(|p| Box::new(p) as _),
+        "mock" => Some(Box::new(MockProvider::new(
+            env_string(env, "LLM_MOCK_REPLY").unwrap_or("Mock summary.".to_string()),
+        ))),
+        other => {
+            console_warn!(
+                "[LLM] ⚠️ Unknown LLM_PROVIDER:{}. LLM stage disabled.",
+                other
+            );
+            None
+        }
+    }
+}
+
+fn summary_prompt(item: &HackerNewsItem, article: Option<&str>) -> String {
+    let mut prompt = format!(
+        "Title: {}\nURL: {}",
+        item.title.as_deref().unwrap_or("Untitled"),
+        item.url.as_deref().unwrap_or("(self post)")
+    );
+    let body = article
+        .map(str::to_string)
+        .or_else(|| item.text.as_deref().map(strip_html));
+    if let Some(body) = body {
+        // Keep the prompt small, the first part of an article carries the gist
+        let body = truncate_graphemes(&body, 6000, |g| g.len());
+        prompt.push_str("\n\n");
+        prompt.push_str(&body);
+    }
+    prompt
+}
+
+/// Summarize one story, from the article text when available or the HN text.
+/// The result is trimmed to fit in KV metadata next to the other fields,
+/// measured as serialized JSON so escaped quotes count twice.
+pub async fn summarize(
+    provider: &dyn LlmProvider,
+    item: &HackerNewsItem,
+    article: Option<&str>,
+) -> Result<String> {
+    let reply = provider
+        .complete(SUMMARY_SYSTEM_PROMPT, &summary_prompt(item, article))
+        .await?;
+    Ok(truncate_graphemes(
+        reply.trim(),
+        LLM_SUMMARY_MAX_BYTES,
+        json_len,
+    ))
+}
+
+fn json_len(s: &str) -> usize {
+    serde_json::to_string(s).map_or(s.len(), |j| j.len() - 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarize_mock(reply: &str, item: &HackerNewsItem) -> String {
+        let provider = MockProvider::new(reply);
+        futures_executor::block_on(summarize(&provider, item, None)).unwrap()
+    }
+
+    #[test]
+    fn summarize_keeps_short_replies() {
+        let item = HackerNewsItem::mock();
+        assert_eq!(
+            summarize_mock("  A short summary.\n", &item),
+            "A short summary."
+        );
+    }
+
+    #[test]
+    fn summarize_trims_on_grapheme_boundary() {
+        let item = HackerNewsItem::mock();
+        // Family emoji: one grapheme, 25 bytes
+        let family = "👨‍👩‍👧‍👦";
+        let summary = summarize_mock(&family.repeat(40), &item);
+        assert!(json_len(&summary) <= LLM_SUMMARY_MAX_BYTES);
+        let kept = summary.trim_end_matches('…');
+        assert!(!kept.is_empty());
+        assert_eq!(kept.len() % family.len(), 0);
+        assert!(summary.ends_with('…'));
+    }
+
+    #[test]
+    fn summarize_counts_escaped_bytes() {
+        let item = HackerNewsItem::mock();
+        let summary = summarize_mock(&"\"".repeat(LLM_SUMMARY_MAX_BYTES), &item);
+        assert!(json_len(&summary) <= LLM_SUMMARY_MAX_BYTES);
+        assert!(summary.len() < LLM_SUMMARY_MAX_BYTES / 2 + 4);
+    }
+
+    #[test]
+    fn summary_prompt_prefers_article_then_hn_text() {
+        let mut item = HackerNewsItem::mock();
+        item.text = Some("<p>Ask HN: what &amp; why?</p>".to_string());
+        let prompt = summary_prompt(&item, Some("Article body"));
+        assert!(prompt.ends_with("\n\nArticle body"));
+        let prompt = summary_prompt(&item, None);
+        assert!(prompt.ends_with("\n\nAsk HN: what & why?"));
+        item.text = None;
+        item.url = None;
+        let prompt = summary_prompt(&item, None);
+        assert_eq!(prompt, "Title: Test Title\nURL: (self post)");
+    }
+}
@@ -0,0 +1,71 @@
+use serde_json::Value;
+use worker::{async_trait::async_trait, *};
+
+use super::LlmProvider;
+use crate::{api::webhook::post_json, config::LLM_MAX_TOKENS, utils::tools::env_string};
+
+const OPENAI_DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const OPENAI_DEFAULT_MODEL: &str = "gpt-4o-mini";
+
+/// Any OpenAI-compatible `/chat/completions` endpoint.
+pub struct OpenAiProvider {
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAiProvider {
+    pub fn from_env(env: &Env, model: Option<String>) -> Option<Self> {
+        let Some(api_key) = env_string(env, "OPENAI_API_KEY") else {
+            console_warn!("[LLM] ⚠️ OPENAI_API_KEY missing in Env. LLM stage disabled.");
+            return None;
+        };
+        let base_url =
+            env_string(env, "OPENAI_BASE_URL").unwrap_or(OPENAI_DEFAULT_BASE_URL.to_string());
+        Some(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model: model.unwrap_or(OPENAI_DEFAULT_MODEL.to_string()),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn complete(&self, system: &str, prompt: &str) -> Result<String> {
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": prompt },
+            ],
+            "max_tokens": LLM_MAX_TOKENS,
+            "temperature": 0.2,
+        });
+        let auth = format!("Bearer {}", self.api_key);
+        let url = format!("{}/chat/completions", self.base_url);
+        let mut res = post_json(&url, &body, &[("Authorization", auth.as_str())]).await?;
+        if !(200..300).contains(&res.status_code()) {
+            console_error!(
+                "[LLM] ❌ OpenAI completion fails. Code: {}.",
+                res.status_code()
+            );
+            return Err(Error::RustError(format!(
+                "Error message: OpenAI-compatible endpoint responded with status:{}",
+                res.status_code()
+            )));
+        }
+        let completion = res.json::<Value>().await?;
+        completion
+            .pointer("/choices/0/message/content")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                Error::RustError("Error message: OpenAI completion has no content".into())
+            })
+    }
+}
@@ -0,0 +1,52 @@
+use serde::Deserialize;
+use worker::{async_trait::async_trait, *};
+
+use super::LlmProvider;
+use crate::config::LLM_MAX_TOKENS;
+
+const WORKERS_AI_BINDING: &str = "AI";
+const WORKERS_AI_DEFAULT_MODEL: &str = "@cf/meta/llama-3.1-8b-instruct";
+
+#[derive(Deserialize)]
+struct TextGenerationOutput {
+    response: Option<String>,
+}
+
+/// Text generation through the Workers AI binding.
+pub struct WorkersAiProvider {
+    ai: Ai,
+    model: String,
+}
+
+impl WorkersAiProvider {
+    pub fn from_env(env: &Env, model: Option<String>) -> Result<Self> {
+        Ok(Self {
+            ai: env.ai(WORKERS_AI_BINDING)?,
+            model: model.unwrap_or(WORKERS_AI_DEFAULT_MODEL.to_string()),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmProvider for WorkersAiProvider {
+    fn name(&self) -> &'static str {
+        "workers_ai"
+    }
+
+    async fn complete(&self, system: &str, prompt: &str) -> Result<String> {
+        let input = serde_json::json!({
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": prompt },
+            ],
+            "max_tokens": LLM_MAX_TOKENS,
+        });
+        let output: TextGenerationOutput = self.ai.run(&self.model, input).await?;
+        output.response.ok_or_else(|| {
+            Error::RustError(format!(
+                "Error message: Workers AI model:{} returned no response",
+                self.model
+            ))
+        })
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::{
+    config::{JOB_LEASE_SECS, KV_MIN_TTL_SECS},
+    keys::LOCK_PREFIX,
+};
+
+/// A job lease held in KV by one run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lease {
+    pub job: String,
+    pub owner: String,
+    /// Unix millis
+    pub acquired_at: u64,
+    pub expires_at: u64,
+}
+
+impl Lease {
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        now_ms >= self.expires_at
+    }
+}
+
+fn lock_key(job: &str) -> String {
+    format!("{}{}", LOCK_PREFIX, job)
+}
+
+pub async fn current_lease(kv: &KvStore, job: &str) -> Result<Option<Lease>> {
+    Ok(kv.get(&lock_key(job)).json::<Lease>().await?)
+}
+
+/// Take the lease for `job`, or return the live lease held by another run.
+/// Expired leases (a run that crashed or overran) are taken over.
+///
+/// KV is eventually consistent, so this is a best-effort lease: the write is
+/// read back to catch the common race, not every one across locations.
+pub async fn acquire(kv: &KvStore, job: &str) -> Result<std::result::Result<Lease, Lease>> {
+    let now = Date::now().as_millis();
+    if let Some(held) = current_lease(kv, job).await? {
+        if !held.is_expired(now) {
+            return Ok(Err(held));
+        }
+        console_warn!(
+            "[Lock] ⚠️ Recover stale lease of job:{} owner:{} expired at:{}",
+            job,
+            held.owner,
+            held.expires_at
+        );
+    }
+    let lease = Lease {
+        job: job.to_string(),
+        owner: uuid::Uuid::new_v4().to_string(),
+        acquired_at: now,
+        expires_at: now + JOB_LEASE_SECS * 1000,
+    };
+    kv.put(&lock_key(job), serde_json::to_string(&lease)?)?
+        .expiration_ttl(JOB_LEASE_SECS.max(KV_MIN_TTL_SECS))
+        .execute()
+        .await?;
+    match current_lease(kv, job).await? {
+        Some(l) if l.owner == lease.owner => {
+            console_log!("[Lock] Acquire lease of job:{} owner:{}", job, lease.owner);
+            Ok(Ok(lease))
+        }
+        Some(other) => Ok(Err(other)),
+        None => Ok(Ok(lease)),
+    }
+}
+
+/// Drop the lease if this run still owns it.
+pub async fn release(kv: &KvStore, lease: &Lease) -> Result<()> {
+    match current_lease(kv, &lease.job).await? {
+        Some(l) if l.owner == lease.owner => {
+            kv.delete(&lock_key(&lease.job)).await?;
+            console_log!(
+                "[Lock] Release lease of job:{} owner:{}",
+                lease.job,
+                lease.owner
+            );
+        }
+        _ => console_warn!(
+            "[Lock] ⚠️ Lease of job:{} owner:{} was taken over before release",
+            lease.job,
+            lease.owner
+        ),
+    }
+    Ok(())
+}
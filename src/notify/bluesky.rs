@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+
+use serde::Deserialize;
+use serde_json::Value;
+use unicode_segmentation::UnicodeSegmentation;
+use worker::{async_trait::async_trait, *};
+
+use super::{ensure_success, post_tail, Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem, api::webhook::post_json, kvm::KVMeta, utils::tools::env_string,
+    utils::tools::truncate_graphemes,
+};
+
+const BSKY_DEFAULT_PDS: &str = "https://bsky.social";
+const BSKY_MAX_GRAPHEMES: usize = 300;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BskySession {
+    access_jwt: String,
+    did: String,
+}
+
+/// Publishes `app.bsky.feed.post` records with link facets via AT Protocol.
+pub struct BlueskyNotifier {
+    pds: String,
+    identifier: String,
+    app_password: String,
+    // Session is created lazily and reused for every item of one run
+    session: RefCell<Option<BskySession>>,
+}
+
+impl BlueskyNotifier {
+    pub fn new(
+        pds: impl Into<String>,
+        identifier: impl Into<String>,
+        app_password: impl Into<String>,
+    ) -> Self {
+        Self {
+            pds: pds.into().trim_end_matches('/').to_string(),
+            identifier: identifier.into(),
+            app_password: app_password.into(),
+            session: RefCell::new(None),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Option<Self> {
+        let identifier = env_string(env, "BLUESKY_HANDLE")?;
+        let Some(app_password) = env_string(env, "BLUESKY_APP_PASSWORD") else {
+            console_warn!("[Notify] ⚠️ BLUESKY_APP_PASSWORD missing in Env. Skip Bluesky sink.");
+            return None;
+        };
+        let pds = env_string(env, "BLUESKY_PDS").unwrap_or(BSKY_DEFAULT_PDS.to_string());
+        Some(Self::new(pds, identifier, app_password))
+    }
+
+    async fn session(&self) -> Result<BskySession> {
+        if let Some(s) = self.session.borrow().as_ref() {
+            return Ok(s.clone());
+        }
+        let url = format!("{}/xrpc/com.atproto.server.createSession", self.pds);
+        let body = serde_json::json!({
+            "identifier": self.identifier,
+            "password": self.app_password,
+        });
+        let mut res = post_json(&url, &body, &[]).await?;
+        ensure_success("BlueskySession", &res)?;
+        let s = res.json::<BskySession>().await?;
+        self.session.replace(Some(s.clone()));
+        Ok(s)
+    }
+
+    fn build_record(&self, payload: &HackerNewsItem, links: &StoryLinks) -> Value {
+        let (text, facets) = build_text(payload, links);
+        serde_json::json!({
+            "$type": "app.bsky.feed.post",
+            "text": text,
+            "facets": facets,
+            "langs": ["en"],
+            "createdAt": String::from(js_sys::Date::new_0().to_iso_string()),
+        })
+    }
+}
+
+/// Post text within the grapheme limit and link facets over its UTF-8 byte ranges.
+fn build_text(payload: &HackerNewsItem, links: &StoryLinks) -> (String, Vec<Value>) {
+    let tail = post_tail(payload, links);
+    let title = truncate_graphemes(
+        payload.title.as_deref().unwrap_or("Untitled"),
+        BSKY_MAX_GRAPHEMES.saturating_sub(tail.graphemes(true).count()),
+        |_| 1,
+    );
+    let text = format!("{}{}", title, tail);
+    let mut facets = Vec::new();
+    let mut from = title.len();
+    for uri in [&links.short_story_url, &links.short_hn_url] {
+        if let Some(pos) = text[from..].find(uri.as_str()) {
+            let start = from + pos;
+            let end = start + uri.len();
+            facets.push(serde_json::json!({
+                "index": { "byteStart": start, "byteEnd": end },
+                "features": [{ "$type": "app.bsky.richtext.facet#link", "uri": uri }],
+            }));
+            from = end;
+        }
+    }
+    (text, facets)
+}
+
+#[async_trait(?Send)]
+impl Notifier for BlueskyNotifier {
+    fn name(&self) -> &'static str {
+        "bluesky"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        _meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        let session = self.session().await?;
+        let body = serde_json::json!({
+            "repo": session.did,
+            "collection": "app.bsky.feed.post",
+            "record": self.build_record(payload, links),
+        });
+        let auth = format!("Bearer {}", session.access_jwt);
+        let url = format!("{}/xrpc/com.atproto.repo.createRecord", self.pds);
+        let mut res = post_json(&url, &body, &[("Authorization", auth.as_str())]).await?;
+        ensure_success("Bluesky", &res)?;
+        let created = res.json::<Value>().await?;
+        // at://<did>/app.bsky.feed.post/<rkey>, enough for deleteRecord later
+        Ok(Delivery::Sent(
+            created
+                .get("uri")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SHORT_LINK_BASE_DEFAULT;
+
+    fn build(title: &str) -> (String, Vec<Value>, StoryLinks) {
+        let mut item = HackerNewsItem::mock();
+        item.title = Some(title.to_string());
+        let links = StoryLinks::new(&item, SHORT_LINK_BASE_DEFAULT).unwrap();
+        let (text, facets) = build_text(&item, &links);
+        (text, facets, links)
+    }
+
+    fn facet_uris<'a>(text: &'a str, facets: &[Value]) -> Vec<&'a str> {
+        facets
+            .iter()
+            .map(|f| {
+                let start = f["index"]["byteStart"].as_u64().unwrap() as usize;
+                let end = f["index"]["byteEnd"].as_u64().unwrap() as usize;
+                assert_eq!(f["features"][0]["uri"].as_str(), Some(&text[start..end]));
+                &text[start..end]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn facets_use_byte_offsets_after_multibyte_title() {
+        let (text, facets, links) = build("Café ☕ 日本語のタイトル 👩‍💻");
+        assert!(text.starts_with("Café ☕ 日本語のタイトル 👩‍💻\n"));
+        assert_eq!(
+            facet_uris(&text, &facets),
+            vec![links.short_story_url.as_str(), links.short_hn_url.as_str()]
+        );
+    }
+
+    #[test]
+    fn long_title_is_cut_to_the_grapheme_limit() {
+        let (text, facets, links) = build(&"👩‍👩‍👧 家族 ".repeat(100));
+        assert_eq!(text.graphemes(true).count(), BSKY_MAX_GRAPHEMES);
+        assert!(text.contains("… ") || text.contains("…\n"));
+        assert_eq!(
+            facet_uris(&text, &facets),
+            vec![links.short_story_url.as_str(), links.short_hn_url.as_str()]
+        );
+    }
+}
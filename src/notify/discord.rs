@@ -0,0 +1,72 @@
+use worker::{async_trait::async_trait, *};
+
+use super::{ensure_success, Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem, api::webhook::post_json, config::APP_NAME, kvm::KVMeta,
+    utils::tools::env_string,
+};
+
+// Hacker News orange
+const EMBED_COLOR: u32 = 0xFF6600;
+
+pub struct DiscordNotifier {
+    webhook_url: String,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: impl Into<String>) -> Self {
+        Self {
+            webhook_url: webhook_url.into(),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Option<Self> {
+        env_string(env, "DISCORD_WEBHOOK_URL").map(Self::new)
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        let mut fields = vec![serde_json::json!({
+            "name": "Comments",
+            "value": format!("[{}+]({})", payload.decendants.unwrap_or(0), links.short_hn_url),
+            "inline": true,
+        })];
+        if let Some(score) = payload.score {
+            fields.insert(
+                0,
+                serde_json::json!({ "name": "Score", "value": format!("{}+", score), "inline": true }),
+            );
+        }
+        let timestamp =
+            js_sys::Date::new(&(payload.timestamp as f64 * 1000.0).into()).to_iso_string();
+        let embed = serde_json::json!({
+            "title": payload.title.as_deref().unwrap_or("Untitled"),
+            "url": links.short_story_url,
+            "description": meta.llm_summary().unwrap_or_default(),
+            "color": EMBED_COLOR,
+            "fields": fields,
+            "footer": { "text": format!("by {}", payload.by) },
+            "timestamp": String::from(timestamp),
+        });
+        let body = serde_json::json!({
+            "username": APP_NAME,
+            "embeds": [embed],
+            // Never ping anyone from story titles
+            "allowed_mentions": { "parse": [] },
+        });
+        let res = post_json(&self.webhook_url, &body, &[]).await?;
+        ensure_success("Discord", &res)?;
+        Ok(Delivery::Sent(None))
+    }
+}
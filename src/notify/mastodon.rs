@@ -0,0 +1,143 @@
+use serde_json::Value;
+use worker::{async_trait::async_trait, *};
+
+use super::{ensure_success, post_tail, Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem, api::webhook::post_json, kvm::KVMeta, utils::tools::env_string,
+    utils::tools::truncate_graphemes,
+};
+
+const MASTODON_MAX_CHARS: usize = 500;
+// Mastodon counts every link as 23 characters regardless of its length
+const MASTODON_URL_CHARS: usize = 23;
+
+/// Publishes statuses through the Mastodon statuses API.
+pub struct MastodonNotifier {
+    instance: String,
+    token: String,
+    visibility: String,
+}
+
+impl MastodonNotifier {
+    pub fn new(
+        instance: impl Into<String>,
+        token: impl Into<String>,
+        visibility: impl Into<String>,
+    ) -> Self {
+        Self {
+            instance: instance.into().trim_end_matches('/').to_string(),
+            token: token.into(),
+            visibility: visibility.into(),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Option<Self> {
+        let instance = env_string(env, "MASTODON_URL")?;
+        let Some(token) = env_string(env, "MASTODON_TOKEN") else {
+            console_warn!("[Notify] ⚠️ MASTODON_TOKEN missing in Env. Skip Mastodon sink.");
+            return None;
+        };
+        let visibility =
+            env_string(env, "MASTODON_VISIBILITY").unwrap_or_else(|| "public".to_string());
+        Some(Self::new(instance, token, visibility))
+    }
+
+    fn build_status(&self, payload: &HackerNewsItem, links: &StoryLinks) -> String {
+        let tail = post_tail(payload, links);
+        let tail_cost = tail.chars().count()
+            - links.short_story_url.chars().count()
+            - links.short_hn_url.chars().count()
+            + 2 * MASTODON_URL_CHARS;
+        let title = truncate_graphemes(
+            payload.title.as_deref().unwrap_or("Untitled"),
+            MASTODON_MAX_CHARS.saturating_sub(tail_cost),
+            |g| g.chars().count(),
+        );
+        format!("{}{}", title, tail)
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for MastodonNotifier {
+    fn name(&self) -> &'static str {
+        "mastodon"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        let body = serde_json::json!({
+            "status": self.build_status(payload, links),
+            "visibility": self.visibility,
+        });
+        let auth = format!("Bearer {}", self.token);
+        let url = format!("{}/api/v1/statuses", self.instance);
+        let mut res = post_json(
+            &url,
+            &body,
+            &[
+                ("Authorization", auth.as_str()),
+                // Mastodon drops duplicate posts with the same key for an hour
+                ("Idempotency-Key", meta.uuid()),
+            ],
+        )
+        .await?;
+        ensure_success("Mastodon", &res)?;
+        let status = res.json::<Value>().await?;
+        // Store the API uri of the status, a DELETE on it removes the post
+        Ok(Delivery::Sent(
+            status
+                .get("id")
+                .and_then(Value::as_str)
+                .map(|id| format!("{}/{}", url, id)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SHORT_LINK_BASE_DEFAULT;
+
+    // Length as Mastodon counts it, every url as MASTODON_URL_CHARS
+    fn counted(status: &str, links: &StoryLinks) -> usize {
+        status.chars().count()
+            - links.short_story_url.chars().count()
+            - links.short_hn_url.chars().count()
+            + 2 * MASTODON_URL_CHARS
+    }
+
+    fn build(title: &str, link_base: &str) -> (String, StoryLinks) {
+        let mut item = HackerNewsItem::mock();
+        item.title = Some(title.to_string());
+        let links = StoryLinks::new(&item, link_base).unwrap();
+        let notifier = MastodonNotifier::new("https://mastodon.social", "token", "public");
+        (notifier.build_status(&item, &links), links)
+    }
+
+    #[test]
+    fn short_title_is_kept() {
+        let (status, links) = build("Ünïcödé title ✨", SHORT_LINK_BASE_DEFAULT);
+        assert!(status.starts_with("Ünïcödé title ✨\n"));
+        assert!(status.ends_with(&links.short_hn_url));
+    }
+
+    #[test]
+    fn long_multibyte_title_fits_the_limit() {
+        let (status, links) = build(&"日本語のタイトル👩‍💻".repeat(60), SHORT_LINK_BASE_DEFAULT);
+        assert_eq!(counted(&status, &links), MASTODON_MAX_CHARS);
+        assert!(status.contains("…\n"));
+        assert!(status.contains(&links.short_story_url));
+    }
+
+    #[test]
+    fn long_urls_count_as_23_chars() {
+        let base = format!("https://{}.example.com", "sub".repeat(30));
+        let (status, links) = build(&"a".repeat(600), &base);
+        assert!(status.chars().count() > MASTODON_MAX_CHARS);
+        assert_eq!(counted(&status, &links), MASTODON_MAX_CHARS);
+    }
+}
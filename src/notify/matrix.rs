@@ -0,0 +1,105 @@
+use worker::{async_trait::async_trait, *};
+
+use super::{ensure_success, tg::build_tg_message, Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem, api::webhook::put_json, kvm::KVMeta, utils::tools::env_string,
+};
+
+/// Posts `m.room.message` events through the client-server `send` endpoint.
+pub struct MatrixNotifier {
+    homeserver: String,
+    room_id: String,
+    access_token: String,
+}
+
+impl MatrixNotifier {
+    pub fn new(
+        homeserver: impl Into<String>,
+        room_id: impl Into<String>,
+        access_token: impl Into<String>,
+    ) -> Self {
+        Self {
+            homeserver: homeserver.into(),
+            room_id: room_id.into(),
+            access_token: access_token.into(),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Option<Self> {
+        let homeserver = env_string(env, "MATRIX_HOMESERVER")?;
+        let Some(room_id) = env_string(env, "MATRIX_ROOM_ID") else {
+            console_warn!("[Notify] ⚠️ MATRIX_ROOM_ID missing in Env. Skip Matrix sink.");
+            return None;
+        };
+        let Some(access_token) = env_string(env, "MATRIX_ACCESS_TOKEN") else {
+            console_warn!("[Notify] ⚠️ MATRIX_ACCESS_TOKEN missing in Env. Skip Matrix sink.");
+            return None;
+        };
+        Some(Self::new(homeserver, room_id, access_token))
+    }
+
+    fn send_url(&self, txn_id: &str) -> Result<Url> {
+        let mut url = Url::parse(&self.homeserver)?;
+        url.path_segments_mut()
+            .map_err(|_| Error::RustError("Error message: invalid Matrix homeserver".into()))?
+            .pop_if_empty()
+            .extend([
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                &self.room_id,
+                "send",
+                "m.room.message",
+                txn_id,
+            ]);
+        Ok(url)
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for MatrixNotifier {
+    fn name(&self) -> &'static str {
+        "matrix"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        // Transaction id is derived from the item so a retried send is deduplicated
+        let txn_id = format!("hn-{}-{}", payload.item_id, meta.uuid());
+        let url = self.send_url(&txn_id)?;
+        let html = build_tg_message(
+            payload,
+            if meta.rising().is_some() {
+                "🚀"
+            } else {
+                "🦀"
+            },
+            &links.short_story_url,
+            &links.short_hn_url,
+            meta.llm_summary(),
+            meta.rising(),
+        )
+        .replace('\n', "<br>");
+        let plain = format!(
+            "{}\nLink: {}\nComments: {}",
+            payload.title.as_deref().unwrap_or("Untitled"),
+            links.short_story_url,
+            links.short_hn_url
+        );
+        let body = serde_json::json!({
+            "msgtype": "m.text",
+            "body": plain,
+            "format": "org.matrix.custom.html",
+            "formatted_body": html,
+        });
+        let auth = format!("Bearer {}", self.access_token);
+        let res = put_json(url.as_str(), &body, &[("Authorization", auth.as_str())]).await?;
+        ensure_success("Matrix", &res)?;
+        Ok(Delivery::Sent(None))
+    }
+}
@@ -0,0 +1,262 @@
+pub mod bluesky;
+pub mod discord;
+pub mod mastodon;
+pub mod matrix;
+pub mod ntfy;
+pub mod slack;
+pub mod tg;
+pub mod webhook;
+
+use worker::{async_trait::async_trait, *};
+
+use crate::{
+    api::hn::HackerNewsItem,
+    config::{Config, MAX_DELIVERY_ATTEMPTS},
+    kvm::{DeliveryReceipt, DeliveryRecord, DeliveryState, KVManager, KVMeta},
+    utils::tools::encode_base56,
+};
+
+pub use bluesky::BlueskyNotifier;
+pub use discord::DiscordNotifier;
+pub use mastodon::MastodonNotifier;
+pub use matrix::MatrixNotifier;
+pub use ntfy::NtfyNotifier;
+pub use slack::SlackNotifier;
+pub use tg::TelegramNotifier;
+pub use webhook::WebhookNotifier;
+
+#[async_trait(?Send)]
+pub trait Notifier {
+    /// Sink name used in logs and delivery receipts.
+    fn name(&self) -> &'static str;
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery>;
+}
+
+#[derive(Clone, Debug)]
+pub enum Delivery {
+    /// Delivered. Carries a reference to the created post (e.g. an at:// uri)
+    /// when the platform supports deleting it later.
+    Sent(Option<String>),
+    /// Not delivered because the sink is rate limited, retry on a later run.
+    Deferred,
+}
+
+#[derive(Debug, Default)]
+pub struct NotifyReport {
+    /// Items delivered to every sink
+    pub notified: usize,
+    /// `sink:item_id` pairs that failed
+    pub failed: Vec<String>,
+    /// Items left pending because a sink deferred them
+    pub deferred: usize,
+}
+
+/// Story and comment links (full and `<base>/s/`, `<base>/c/` short form) for one item.
+#[derive(Clone, Debug)]
+pub struct StoryLinks {
+    pub hn_url: String,
+    pub short_hn_url: String,
+    pub story_url: String,
+    pub short_story_url: String,
+}
+
+impl StoryLinks {
+    pub fn new(payload: &HackerNewsItem, link_base: &str) -> Result<Self> {
+        let link_base = link_base.trim_end_matches('/');
+        let story_id = payload.item_id.to_string();
+        let short_id = encode_base56(payload.item_id);
+        // Comment url group
+        let hn_url: String =
+            Url::parse_with_params("https://news.ycombinator.com/item/", &[("id", &story_id)])?
+                .to_string();
+        let short_hn_url: String = format!("{}/c/{}", link_base, &short_id);
+        // Story url group
+        let story_url: String = payload.url.as_deref().unwrap_or(&hn_url).to_string();
+        let short_story_url: String = payload
+            .url
+            .as_deref()
+            .map(|_| format!("{}/s/{}", link_base, &short_id))
+            .unwrap_or(short_hn_url.clone());
+        Ok(Self {
+            hn_url,
+            short_hn_url,
+            story_url,
+            short_story_url,
+        })
+    }
+}
+
+/// Plain text tail shared by text-only platforms: score, author and links.
+pub(crate) fn post_tail(payload: &HackerNewsItem, links: &StoryLinks) -> String {
+    let score_part = payload
+        .score
+        .map(|s| format!("Score: {}+ · ", s))
+        .unwrap_or_default();
+    format!(
+        "\n\n{}by {}\n🔗 {}\n💬 {}",
+        score_part, payload.by, links.short_story_url, links.short_hn_url
+    )
+}
+
+/// Build every sink whose secrets/vars are present in Env.
+pub fn notifiers_from_env(env: &Env, config: &Config) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    if let Some(tg) = config.telegram.as_ref() {
+        let n = TelegramNotifier::from_config(tg, env.kv(&config.kv_binding).ok());
+        notifiers.push(Box::new(n));
+    }
+    if let Some(n) = DiscordNotifier::from_env(env) {
+        notifiers.push(Box::new(n));
+    }
+    if let Some(n) = SlackNotifier::from_env(env) {
+        notifiers.push(Box::new(n));
+    }
+    if let Some(n) = WebhookNotifier::from_env(env) {
+        notifiers.push(Box::new(n));
+    }
+    if let Some(n) = MatrixNotifier::from_env(env) {
+        notifiers.push(Box::new(n));
+    }
+    if let Some(n) = NtfyNotifier::from_env(env) {
+        notifiers.push(Box::new(n));
+    }
+    if let Some(n) = MastodonNotifier::from_env(env) {
+        notifiers.push(Box::new(n));
+    }
+    if let Some(n) = BlueskyNotifier::from_env(env) {
+        notifiers.push(Box::new(n));
+    }
+    notifiers
+}
+
+/// Sinks available to a request or job. Notifiers hold per-run state (rate
+/// limits, sessions), so they are built on demand rather than shared.
+#[derive(Clone, Debug)]
+pub struct NotifierRegistry {
+    env: Env,
+}
+
+impl NotifierRegistry {
+    pub fn new(env: Env) -> Self {
+        Self { env }
+    }
+
+    /// Every sink configured in Env, with `config` for the Telegram settings
+    /// that the KV settings document can override.
+    pub fn build(&self, config: &Config) -> Vec<Box<dyn Notifier>> {
+        notifiers_from_env(&self.env, config)
+    }
+}
+
+/// Second phase of delivery. Items are expected to be cached as pending; after
+/// every sink was tried the delivery record with its receipts is written once,
+/// keeping to one write per key. Sinks holding a receipt are skipped, which
+/// makes retrying pending items safe.
+pub async fn dispatch(
+    notifiers: &[Box<dyn Notifier>],
+    kvm: &KVManager,
+    payloads: &mut [(HackerNewsItem, KVMeta, DeliveryRecord)],
+    link_base: &str,
+) -> Result<NotifyReport> {
+    let mut report = NotifyReport::default();
+    if notifiers.is_empty() {
+        // Callers check first, pending records would otherwise never settle
+        return Err(Error::RustError(
+            "Error message: no notifier to dispatch to".to_string(),
+        ));
+    }
+    console_log!(
+        "[Notify] Dispatch to sinks:{:?}",
+        notifiers.iter().map(|n| n.name()).collect::<Vec<_>>()
+    );
+    for (p, meta, record) in payloads {
+        console_log!(
+            "[Notify] Title: \"{}\" --- By: {}\n[Notify] Link: {}",
+            &p.title.as_deref().unwrap_or_default(),
+            &p.by,
+            &p.url.as_deref().unwrap_or_default()
+        );
+        let links = StoryLinks::new(p, link_base)?;
+        let mut failed = false;
+        let mut deferred = false;
+        for n in notifiers {
+            if record.is_delivered_to(n.name()) {
+                continue;
+            }
+            // One failing sink should not block the others
+            match n.notify(p, meta, &links).await {
+                Ok(Delivery::Sent(message_id)) => {
+                    console_log!(
+                        "[Notify] Sink:{} delivered id:{} message:{:?}",
+                        n.name(),
+                        p.item_id,
+                        message_id
+                    );
+                    record.with_receipt(DeliveryReceipt {
+                        channel: n.name().to_string(),
+                        message_id,
+                        timestamp: Date::now().as_millis() / 1000,
+                    });
+                }
+                Ok(Delivery::Deferred) => deferred = true,
+                Err(e) => {
+                    console_error!(
+                        "[Notify] ❌ Sink:{} fails for id:{}. Error: {}",
+                        n.name(),
+                        p.item_id,
+                        e
+                    );
+                    report.failed.push(format!("{}:{}", n.name(), p.item_id));
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            record.add_attempt();
+        }
+        if notifiers.iter().all(|n| record.is_delivered_to(n.name())) {
+            record.with_state(DeliveryState::Delivered);
+            report.notified += 1;
+        } else if record.attempts() >= MAX_DELIVERY_ATTEMPTS {
+            console_error!(
+                "[Notify] ❌ Give up id:{} after {} attempts",
+                p.item_id,
+                record.attempts()
+            );
+            record.with_state(DeliveryState::Failed);
+        } else if deferred {
+            report.deferred += 1;
+        }
+        // Keep going on a failed write, the other items are independent
+        if let Err(e) = kvm.put_delivery(p.item_id, record).await {
+            console_error!(
+                "[Notify] ❌ Store delivery record id:{} fails. Error: {}",
+                p.item_id,
+                e
+            );
+            report.failed.push(format!("delivery:{}", p.item_id));
+        }
+    }
+    Ok(report)
+}
+
+pub(crate) fn ensure_success(sink: &str, res: &Response) -> Result<()> {
+    if !(200..300).contains(&res.status_code()) {
+        console_error!(
+            "[Notify] ❌ notify{} fails. Code: {}.",
+            sink,
+            res.status_code()
+        );
+        return Err(Error::RustError(format!(
+            "Error message: {} sink responded with status:{}",
+            sink,
+            res.status_code()
+        )));
+    }
+    Ok(())
+}
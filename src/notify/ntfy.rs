@@ -0,0 +1,100 @@
+use worker::{async_trait::async_trait, *};
+
+use super::{ensure_success, Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem, api::webhook::post_json, kvm::KVMeta, utils::tools::env_string,
+};
+
+const NTFY_DEFAULT_SERVER: &str = "https://ntfy.sh";
+const NTFY_DEFAULT_PRIORITY: u8 = 3;
+// Stories at or above this score are bumped one priority level
+const NTFY_HOT_SCORE: u64 = 500;
+
+/// Publishes to an ntfy topic with the JSON publish API (POST to server root).
+pub struct NtfyNotifier {
+    server: String,
+    topic: String,
+    token: Option<String>,
+    priority: u8,
+}
+
+impl NtfyNotifier {
+    pub fn new(
+        server: impl Into<String>,
+        topic: impl Into<String>,
+        token: Option<String>,
+        priority: u8,
+    ) -> Self {
+        Self {
+            server: server.into(),
+            topic: topic.into(),
+            token,
+            priority: priority.clamp(1, 5),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Option<Self> {
+        let topic = env_string(env, "NTFY_TOPIC")?;
+        let server = env_string(env, "NTFY_URL").unwrap_or(NTFY_DEFAULT_SERVER.to_string());
+        let token = env_string(env, "NTFY_TOKEN");
+        let priority = env_string(env, "NTFY_PRIORITY")
+            .and_then(|p| p.parse::<u8>().ok())
+            .unwrap_or(NTFY_DEFAULT_PRIORITY);
+        Some(Self::new(server, topic, token, priority))
+    }
+
+    fn priority_for(&self, payload: &HackerNewsItem) -> u8 {
+        if payload.score.unwrap_or(0) >= NTFY_HOT_SCORE {
+            (self.priority + 1).min(5)
+        } else {
+            self.priority
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for NtfyNotifier {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        let mut message = payload
+            .score
+            .map(|s| format!("Score: {}+ · by {}", s, payload.by))
+            .unwrap_or(format!("by {}", payload.by));
+        if let Some(summary) = meta.llm_summary() {
+            message.push_str("\n\n");
+            message.push_str(summary);
+        }
+        let body = serde_json::json!({
+            "topic": self.topic,
+            "title": payload.title.as_deref().unwrap_or("Untitled"),
+            "message": message,
+            "priority": self.priority_for(payload),
+            "tags": ["crab", "newspaper"],
+            "click": links.short_story_url,
+            "actions": [{
+                "action": "view",
+                "label": payload.decendants.map_or_else(
+                    || "Comments".to_string(),
+                    |cc| format!("Comments {}+", cc),
+                ),
+                "url": links.short_hn_url,
+            }],
+        });
+        let auth = self.token.as_ref().map(|t| format!("Bearer {}", t));
+        let headers: Vec<(&str, &str)> = auth
+            .as_deref()
+            .map(|a| vec![("Authorization", a)])
+            .unwrap_or_default();
+        let res = post_json(&self.server, &body, &headers).await?;
+        ensure_success("Ntfy", &res)?;
+        Ok(Delivery::Sent(None))
+    }
+}
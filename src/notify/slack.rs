@@ -0,0 +1,95 @@
+use worker::{async_trait::async_trait, *};
+
+use super::{ensure_success, Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem, api::webhook::post_json, kvm::KVMeta, utils::tools::env_string,
+};
+
+pub struct SlackNotifier {
+    webhook_url: String,
+}
+
+impl SlackNotifier {
+    pub fn new(webhook_url: impl Into<String>) -> Self {
+        Self {
+            webhook_url: webhook_url.into(),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Option<Self> {
+        env_string(env, "SLACK_WEBHOOK_URL").map(Self::new)
+    }
+}
+
+// Slack mrkdwn only requires escaping these three
+fn escape_mrkdwn(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[async_trait(?Send)]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        let title = escape_mrkdwn(payload.title.as_deref().unwrap_or("Untitled"));
+        let score_part = payload
+            .score
+            .map(|s| format!("Score: {}+ · ", s))
+            .unwrap_or_default();
+        let mut blocks = vec![serde_json::json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!(
+                    "*<{}|{}>*\n{}by {}",
+                    links.short_story_url, title, score_part, escape_mrkdwn(&payload.by)
+                ),
+            },
+        })];
+        if let Some(summary) = meta.llm_summary() {
+            blocks.push(serde_json::json!({
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": escape_mrkdwn(summary) }],
+            }));
+        }
+        blocks.push(serde_json::json!({
+            "type": "actions",
+            "elements": [
+                {
+                    "type": "button",
+                    "text": { "type": "plain_text", "text": "Read" },
+                    "url": links.story_url,
+                },
+                {
+                    "type": "button",
+                    "text": {
+                        "type": "plain_text",
+                        "text": payload.decendants.map_or_else(
+                            || "Comments".to_string(),
+                            |cc| format!("Comments {}+", cc),
+                        ),
+                    },
+                    "url": links.hn_url,
+                },
+            ],
+        }));
+        let body = serde_json::json!({
+            // Fallback for notifications and clients without Block Kit
+            "text": payload.title.as_deref().unwrap_or("Untitled"),
+            "unfurl_links": false,
+            "blocks": blocks,
+        });
+        let res = post_json(&self.webhook_url, &body, &[]).await?;
+        ensure_success("Slack", &res)?;
+        Ok(Delivery::Sent(None))
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Write,
+    time::Duration,
+};
+use worker::{async_trait::async_trait, *};
+
+use super::{Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem,
+    api::tg::{TelegramClient, TgMessage},
+    article::cached_article_image,
+    config::TelegramConfig,
+    kvm::KVMeta,
+    utils::rate_limit::TokenBucket,
+};
+
+// Telegram allows about 20 messages per minute to the same group
+const TG_CHAT_MSGS_PER_MIN: u32 = 20;
+// Waits longer than this defer the item to the next cron tick instead
+const TG_MAX_WAIT_SECS: u64 = 30;
+const TG_MAX_RETRIES: u8 = 2;
+// sendPhoto captions are limited to 1024 characters after entity parsing
+const TG_CAPTION_MAX_CHARS: usize = 1024;
+
+pub struct TelegramNotifier {
+    client: TelegramClient,
+    chat_id: String,
+    bucket: RefCell<TokenBucket>,
+    // Send stories with an og:image through sendPhoto, read from the article cache
+    preview_images: Option<KvStore>,
+    // Once one item is deferred, defer the rest too so the channel keeps order
+    deferring: Cell<bool>,
+}
+
+impl TelegramNotifier {
+    pub fn new(token: impl Into<String>, chat_id: impl Into<String>) -> Self {
+        Self {
+            client: TelegramClient::new(token),
+            chat_id: chat_id.into(),
+            bucket: RefCell::new(TokenBucket::per_minute(
+                TG_CHAT_MSGS_PER_MIN,
+                Date::now().as_millis(),
+            )),
+            preview_images: None,
+            deferring: Cell::new(false),
+        }
+    }
+
+    /// Look up og:image urls in the article cache of `kv` and send them as photos.
+    pub fn with_preview_images(mut self, kv: Option<KvStore>) -> Self {
+        self.preview_images = kv;
+        self
+    }
+
+    async fn preview_image(&self, item_id: u64) -> Option<String> {
+        let kv = self.preview_images.as_ref()?;
+        cached_article_image(kv, item_id)
+            .await
+            .inspect_err(|e| {
+                console_warn!(
+                    "[Notify] ⚠️ notifyTg reads article cache for id:{} fails. Error: {}",
+                    item_id,
+                    e
+                )
+            })
+            .ok()
+            .flatten()
+    }
+
+    fn defer(&self, payload: &HackerNewsItem, reason: &str) -> Delivery {
+        console_warn!(
+            "[Notify] ⚠️ notifyTg defers id:{} to next run. Reason: {}",
+            payload.item_id,
+            reason
+        );
+        self.deferring.set(true);
+        Delivery::Deferred
+    }
+
+    /// Wait for the per-chat token bucket and retry on 429 with `retry_after`.
+    /// Sends a photo with `msg` as caption when `photo` is set.
+    /// Returns `None` when the item should be deferred.
+    async fn send_rate_limited(
+        &self,
+        msg: &str,
+        reply_markup: &serde_json::Value,
+        photo: Option<&str>,
+    ) -> Result<Option<TgMessage>> {
+        // Another send may take the token while this one sleeps, so wait
+        // until one is granted, within TG_MAX_WAIT_SECS overall
+        let mut waited_ms = 0;
+        loop {
+            let acquired = self
+                .bucket
+                .borrow_mut()
+                .try_acquire(Date::now().as_millis());
+            let Err(wait_ms) = acquired else {
+                break;
+            };
+            waited_ms += wait_ms;
+            if waited_ms > TG_MAX_WAIT_SECS * 1000 {
+                return Ok(None);
+            }
+            console_log!("[Notify] notifyTg waits {}ms for chat token", wait_ms);
+            Delay::from(Duration::from_millis(wait_ms)).await;
+        }
+        let mut attempt = 0;
+        loop {
+            let sent = match photo {
+                Some(photo) => {
+                    self.client
+                        .send_photo(&self.chat_id, photo, msg, Some(reply_markup.clone()))
+                        .await
+                }
+                None => {
+                    self.client
+                        .send_message(&self.chat_id, msg, Some(reply_markup.clone()))
+                        .await
+                }
+            };
+            match sent {
+                Ok(sent) => return Ok(Some(sent)),
+                Err(e) => match e.retry_after() {
+                    Some(secs) if secs <= TG_MAX_WAIT_SECS && attempt < TG_MAX_RETRIES => {
+                        attempt += 1;
+                        console_warn!(
+                            "[Notify] ⚠️ notifyTg got 429, retry {} after {}s",
+                            attempt,
+                            secs
+                        );
+                        Delay::from(Duration::from_secs(secs)).await;
+                    }
+                    Some(_) => return Ok(None),
+                    None => return Err(e.into()),
+                },
+            }
+        }
+    }
+
+    /// `kv` holds the article cache, only used when preview images are on.
+    pub fn from_config(config: &TelegramConfig, kv: Option<KvStore>) -> Self {
+        Self::new(config.bot_token.expose(), config.chat_id.clone())
+            .with_preview_images(kv.filter(|_| config.preview_images))
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        if self.deferring.get() {
+            return Ok(self.defer(payload, "an earlier item was deferred"));
+        }
+        let cc_option = payload.decendants;
+        // Build buttons
+        let buttons = serde_json::json!([
+            {
+                "text": payload.url.as_deref().map_or_else(
+                    || "Read",
+                    |_| "Read HN",
+                ),
+                "url": links.story_url,
+            },
+            {
+                "text": cc_option.map_or_else(
+                    || "Comments".to_string(),
+                    |cc| format!("Comments {}+", cc),
+                ),
+                "url": links.short_hn_url,
+            },
+        ]);
+        let reply_markup = serde_json::json!({
+            "inline_keyboard": [buttons],
+        });
+
+        // Build message
+        let msg = build_tg_message(
+            payload,
+            if meta.rising().is_some() {
+                "🚀 "
+            } else {
+                "🦀 "
+            },
+            &links.short_story_url,
+            &links.short_hn_url,
+            meta.llm_summary(),
+            meta.rising(),
+        );
+        // A message too long for a caption goes out as text without the image
+        let photo = if fits_caption(&msg) {
+            self.preview_image(payload.item_id).await
+        } else {
+            None
+        };
+        let sent = match photo {
+            Some(photo) => match self
+                .send_rate_limited(&msg, &reply_markup, Some(&photo))
+                .await
+            {
+                Ok(sent) => sent,
+                Err(e) => {
+                    // Telegram could not fetch or use the image, post as text instead
+                    console_warn!(
+                        "[Notify] ⚠️ notifyTg sendPhoto fails for id:{}, fallback to text. Error: {}",
+                        payload.item_id,
+                        e
+                    );
+                    self.send_rate_limited(&msg, &reply_markup, None).await?
+                }
+            },
+            None => self.send_rate_limited(&msg, &reply_markup, None).await?,
+        };
+        match sent {
+            Some(sent) => Ok(Delivery::Sent(Some(sent.message_id.to_string()))),
+            None => Ok(self.defer(payload, "rate limited")),
+        }
+    }
+}
+
+fn fits_caption(msg: &str) -> bool {
+    caption_len(msg) <= TG_CAPTION_MAX_CHARS
+}
+
+// Visible length Telegram checks a caption against: tags dropped, entities
+// count as one character, measured in UTF-16 code units
+fn caption_len(html: &str) -> usize {
+    let mut len = 0;
+    let mut in_tag = false;
+    let mut in_entity = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            '&' => {
+                in_entity = true;
+                len += 1;
+            }
+            ';' if in_entity => in_entity = false,
+            _ if in_entity => {}
+            c => len += c.len_utf16(),
+        }
+    }
+    len
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub(crate) fn build_tg_message(
+    payload: &HackerNewsItem,
+    status_emoji: &str,
+    short_story_url: &str,
+    short_hn_url: &str,
+    summary: Option<&str>,
+    rising: Option<&str>,
+) -> String {
+    let mut msg = String::new();
+    // Add title
+    let title = escape_html(payload.title.as_deref().unwrap_or("Untitled"));
+
+    // Add Score
+    let score_part = payload
+        .score
+        .map(|s| format!("Score: {}+", s))
+        .unwrap_or_default();
+    let by_part = format!("by {}", payload.by);
+
+    let _ = write!(&mut msg, "<b>{}</b> {}", title, status_emoji);
+    if score_part.is_empty() {
+        let _ = write!(&mut msg, "\n({})", by_part);
+    } else {
+        let _ = write!(&mut msg, "\n({} · {})", score_part, by_part);
+    }
+    // Mark early alerts, they are posted before reaching the score threshold
+    if let Some(rising) = rising {
+        let _ = write!(&mut msg, "\n📈 <b>Rising:</b> {}", escape_html(rising));
+    }
+    // Add Story and Comments Link
+    let _ = write!(
+        &mut msg,
+        "\n\n<b>Link:</b> {}\n<b>Comments:</b> {}",
+        short_story_url, short_hn_url
+    );
+    // Add LLM summary
+    if let Some(summary) = summary {
+        let _ = write!(&mut msg, "\n\n<i>{}</i>", escape_html(summary));
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LLM_SUMMARY_MAX_BYTES;
+
+    fn message(summary: Option<&str>) -> String {
+        build_tg_message(
+            &HackerNewsItem::mock(),
+            "🦀 ",
+            "https://readhacker.news/s/2",
+            "https://readhacker.news/c/2",
+            summary,
+            None,
+        )
+    }
+
+    #[test]
+    fn caption_len_counts_visible_utf16() {
+        assert_eq!(caption_len("<b>bold</b> <i>x</i>"), 6);
+        assert_eq!(caption_len("a &amp; b &lt;c&gt;"), 9);
+        // Astral emoji take two UTF-16 code units
+        assert_eq!(caption_len("🦀é"), 3);
+    }
+
+    #[test]
+    fn longest_summary_fits_a_caption() {
+        let summary = "&".repeat(LLM_SUMMARY_MAX_BYTES);
+        let msg = message(Some(&summary));
+        assert!(msg.len() > TG_CAPTION_MAX_CHARS);
+        assert!(fits_caption(&msg), "{}", caption_len(&msg));
+    }
+
+    #[test]
+    fn long_caption_falls_back_to_text() {
+        let summary = "word ".repeat(250);
+        let msg = message(Some(&summary));
+        assert!(!fits_caption(&msg), "{}", caption_len(&msg));
+        assert!(fits_caption(&message(None)));
+    }
+}
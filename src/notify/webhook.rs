@@ -0,0 +1,82 @@
+use worker::{async_trait::async_trait, *};
+
+use super::{ensure_success, Delivery, Notifier, StoryLinks};
+use crate::{
+    api::hn::HackerNewsItem, api::webhook::post_raw, kvm::KVMeta, utils::tools::env_string,
+    utils::tools::hmac_sha256_hex,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-HN-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-HN-Timestamp";
+
+/// Generic JSON webhook. When a secret is configured the body is signed with
+/// HMAC-SHA256 over `"{timestamp}.{body}"` and sent as `sha256=<hex>`.
+pub struct WebhookNotifier {
+    url: String,
+    secret: Option<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>, secret: Option<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret,
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Option<Self> {
+        let url = env_string(env, "WEBHOOK_URL")?;
+        let secret = env_string(env, "WEBHOOK_SECRET");
+        if secret.is_none() {
+            console_warn!(
+                "[Notify] ⚠️ WEBHOOK_SECRET missing in Env. Webhook bodies will not be signed."
+            );
+        }
+        Some(Self::new(url, secret))
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn notify(
+        &self,
+        payload: &HackerNewsItem,
+        meta: &KVMeta,
+        links: &StoryLinks,
+    ) -> Result<Delivery> {
+        let body = serde_json::json!({
+            "event": "story",
+            "item": payload,
+            "meta": meta,
+            "links": {
+                "story": links.story_url,
+                "short_story": links.short_story_url,
+                "comments": links.hn_url,
+                "short_comments": links.short_hn_url,
+            },
+        })
+        .to_string();
+        let timestamp = (Date::now().as_millis() / 1000).to_string();
+        let res = match &self.secret {
+            Some(secret) => {
+                let signed = format!("{}.{}", timestamp, body);
+                let signature = format!(
+                    "sha256={}",
+                    hmac_sha256_hex(secret.as_bytes(), signed.as_bytes())
+                );
+                let headers = [
+                    (TIMESTAMP_HEADER, timestamp.as_str()),
+                    (SIGNATURE_HEADER, signature.as_str()),
+                ];
+                post_raw(&self.url, &body, &headers).await?
+            }
+            None => post_raw(&self.url, &body, &[(TIMESTAMP_HEADER, timestamp.as_str())]).await?,
+        };
+        ensure_success("Webhook", &res)?;
+        Ok(Delivery::Sent(None))
+    }
+}
@@ -1,11 +1,29 @@
 use axum::{
-    extract::Path,
-    http::{StatusCode, Uri},
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        Path, Query, State,
+    },
+    http::{HeaderMap, StatusCode, Uri},
     response::{IntoResponse, Redirect},
+    Json,
 };
-use worker::{console_log, console_warn};
+use worker::{console_error, console_log, console_warn};
 
-use crate::api::hn::LiveDataKey;
+use serde::Deserialize;
+
+use crate::{
+    api::hn::{HackerNewsItem, LiveDataKey},
+    config::{HISTORY_LIMIT_MAX, RUN_LOG_MAX, STATUS_RUNS_DEFAULT},
+    history::HistoryStore,
+    jobs::{job_by_name, job_statuses, run_job, Health, JobError},
+    settings::{load_settings, save_settings, Settings, SettingsError, SETTINGS_SCHEMA},
+    state::AppState,
+    stats::{do_not_track, flush_clicks, get_clicks, record_click, ClickKind},
+    trajectory::load_trajectories,
+    utils::short_id::ShortId,
+};
+
+const HN_ITEM_URL: &str = "https://news.ycombinator.com/item?id=";
 
 pub async fn get_root() -> &'static str {
     console_log!("[Router] Trigger get_root");
@@ -52,6 +70,354 @@ pub async fn get_forward_item(Path((item, id)): Path<(String, u64)>) -> impl Int
         .into_response()
 }
 
+#[worker::send]
+pub async fn get_short_story(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    code: Result<Path<ShortId>, PathRejection>,
+) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_short_story");
+    let id = match code {
+        Ok(Path(code)) => code.id(),
+        Err(e) => {
+            console_warn!("[Router] ⚠️ Invalid short code. {}", e.body_text());
+            return (StatusCode::NOT_FOUND, "Unknown short link").into_response();
+        }
+    };
+    track_click(&state, &headers, id, ClickKind::Story);
+    // Posted items are still cached, the HN API is only asked for older ones
+    let cached = match state
+        .kvm
+        .get_json::<HackerNewsItem>(state.kvm.item_key(id))
+        .await
+    {
+        Ok(item) => item,
+        Err(e) => {
+            console_warn!("[Router] ⚠️ Read cached id:{} fails. Error: {}", id, e);
+            None
+        }
+    };
+    let item = match cached {
+        Some(item) => item,
+        None => match state.hn.fetch_item(id).await {
+            Ok(item) => item,
+            Err(e) => {
+                console_error!("[Router] ❌ Fetch item id:{} fails. Error: {}", id, e);
+                return (StatusCode::BAD_GATEWAY, "Failed to fetch item").into_response();
+            }
+        },
+    };
+    // Ask HN and other text posts have no url, their story is the comments page
+    match item.url {
+        Some(url) => Redirect::to(&url).into_response(),
+        None => Redirect::to(&format!("{}{}", HN_ITEM_URL, id)).into_response(),
+    }
+}
+
+pub async fn get_short_comments(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    code: Result<Path<ShortId>, PathRejection>,
+) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_short_comments");
+    let id = match code {
+        Ok(Path(code)) => code.id(),
+        Err(e) => {
+            console_warn!("[Router] ⚠️ Invalid short code. {}", e.body_text());
+            return (StatusCode::NOT_FOUND, "Unknown short link").into_response();
+        }
+    };
+    track_click(&state, &headers, id, ClickKind::Comments);
+    Redirect::to(&format!("{}{}", HN_ITEM_URL, id)).into_response()
+}
+
+// Counted in memory and flushed after the response is sent, so tracking
+// never delays the redirect
+fn track_click(state: &AppState, headers: &HeaderMap, id: u64, kind: ClickKind) {
+    if do_not_track(headers) || !record_click(id, kind) {
+        return;
+    }
+    let kv = state.kvm.kv().clone();
+    state.wait_until(async move {
+        if let Err(e) = flush_clicks(&kv).await {
+            console_warn!("[Router] ⚠️ Flush clicks fails. Error: {}", e);
+        }
+    });
+}
+
+#[worker::send]
+pub async fn get_stats(State(state): State<AppState>, Path(id): Path<u64>) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_stats");
+    match get_clicks(state.kvm.kv(), id).await {
+        Ok(clicks) => Json(serde_json::json!({
+            "id": id,
+            "short_id": ShortId::new(id),
+            "story": clicks.story,
+            "comments": clicks.comments,
+            "total": clicks.total(),
+        }))
+        .into_response(),
+        Err(e) => {
+            console_error!("[Router] ❌ Read clicks id:{} fails. Error: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read stats").into_response()
+        }
+    }
+}
+
+#[worker::send]
+pub async fn get_item_history(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_item_history");
+    match load_trajectories(state.kvm.kv()).await {
+        Ok(trajectories) => Json(serde_json::json!({
+            "id": id,
+            "points": trajectories.points(id),
+        }))
+        .into_response(),
+        Err(e) => {
+            console_error!("[Router] ❌ Read trajectory id:{} fails. Error: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read history").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RunParams {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[worker::send]
+pub async fn post_run_job(
+    State(state): State<AppState>,
+    Path(job): Path<String>,
+    Query(params): Query<RunParams>,
+) -> impl IntoResponse {
+    console_log!(
+        "[Router] Trigger post_run_job job:{} dry_run:{}",
+        job,
+        params.dry_run
+    );
+    let spec = match job_by_name(&job) {
+        Ok(spec) => spec,
+        Err(e) => {
+            console_warn!("[Router] ⚠️ {}", e);
+            return (StatusCode::NOT_FOUND, e.to_string()).into_response();
+        }
+    };
+    match run_job(&state, spec, params.dry_run).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e @ JobError::NotImplemented(_)) => {
+            console_warn!("[Router] ⚠️ {}", e);
+            (StatusCode::NOT_IMPLEMENTED, e.to_string()).into_response()
+        }
+        Err(e @ JobError::Locked(_)) => {
+            console_warn!("[Router] ⚠️ {}", e);
+            (StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        Err(JobError::Config(e)) => {
+            console_error!("[Router] ❌ Run job:{} refused. {}", job, e);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({ "config_errors": e.0 })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            console_error!("[Router] ❌ Run job:{} fails. Error: {}", job, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[worker::send]
+pub async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_config");
+    let effective = match state.effective_config().await {
+        Ok(c) => c,
+        Err(e) => {
+            console_error!("[Router] ❌ Load settings fails. Error: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    // Secrets serialize as `<redacted>`
+    let mut config = serde_json::to_value(effective.as_ref()).unwrap_or_default();
+    config["config_errors"] = serde_json::json!(config_errors(&state));
+    Json(config).into_response()
+}
+
+#[worker::send]
+pub async fn get_settings(State(state): State<AppState>) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_settings");
+    match load_settings(state.kvm.kv()).await {
+        Ok(doc) => {
+            let effective = doc.settings.apply(&state.config);
+            Json(serde_json::json!({
+                "version": doc.version,
+                "updated_at": doc.updated_at,
+                "settings": doc.settings,
+                "effective": effective,
+                "schema": serde_json::from_str::<serde_json::Value>(SETTINGS_SCHEMA)
+                    .unwrap_or_default(),
+            }))
+            .into_response()
+        }
+        Err(e) => {
+            console_error!("[Router] ❌ Load settings fails. Error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PutSettings {
+    /// Version the client read, the write is refused if it moved on
+    pub version: u64,
+    pub settings: Settings,
+}
+
+#[worker::send]
+pub async fn put_settings(
+    State(state): State<AppState>,
+    body: Result<Json<PutSettings>, JsonRejection>,
+) -> impl IntoResponse {
+    console_log!("[Router] Trigger put_settings");
+    let Json(body) = match body {
+        Ok(b) => b,
+        Err(e) => {
+            console_warn!("[Router] ⚠️ Invalid settings body. {}", e.body_text());
+            return (StatusCode::UNPROCESSABLE_ENTITY, e.body_text()).into_response();
+        }
+    };
+    match save_settings(state.kvm.kv(), body.version, body.settings).await {
+        Ok(doc) => Json(doc).into_response(),
+        Err(SettingsError::Conflict(current)) => {
+            console_warn!(
+                "[Router] ⚠️ Settings version conflict. got:{} current:{}",
+                body.version,
+                current.version
+            );
+            (StatusCode::CONFLICT, Json(current)).into_response()
+        }
+        Err(SettingsError::Invalid(e)) => {
+            console_warn!("[Router] ⚠️ {}", e);
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({ "errors": e.0 })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            console_error!("[Router] ❌ Save settings fails. Error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StatusParams {
+    pub limit: Option<usize>,
+}
+
+fn config_errors(state: &AppState) -> &[String] {
+    state.config_error.as_ref().map_or(&[], |e| &e.0)
+}
+
+/// Public health check: last runs per job, config problems and an overall
+/// verdict. Answers 503 when a job is stale, its last run failed or the
+/// config is invalid.
+#[worker::send]
+pub async fn get_status(
+    State(state): State<AppState>,
+    Query(params): Query<StatusParams>,
+) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_status");
+    let limit = params.limit.unwrap_or(STATUS_RUNS_DEFAULT).min(RUN_LOG_MAX);
+    match job_statuses(state.kvm.kv(), limit).await {
+        Ok(jobs) => {
+            let health = jobs
+                .iter()
+                .map(|j| j.health)
+                .max()
+                .unwrap_or(Health::Unknown);
+            let code = match health {
+                Health::Stale | Health::Degraded => StatusCode::SERVICE_UNAVAILABLE,
+                _ if state.config_error.is_some() => StatusCode::SERVICE_UNAVAILABLE,
+                Health::Ok | Health::Unknown => StatusCode::OK,
+            };
+            (
+                code,
+                Json(serde_json::json!({
+                    "health": health,
+                    "config_errors": config_errors(&state),
+                    "jobs": jobs,
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            console_error!("[Router] ❌ Load job status fails. Error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryParams {
+    pub days: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+fn history_since(days: u64) -> u64 {
+    (worker::Date::now().as_millis() / 1000).saturating_sub(days.saturating_mul(86400))
+}
+
+#[worker::send]
+pub async fn get_history_top(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryParams>,
+) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_history_top");
+    let Some(history) = HistoryStore::from_env(&state.env) else {
+        return (StatusCode::NOT_FOUND, "History store is not configured").into_response();
+    };
+    let since = history_since(params.days.unwrap_or(28));
+    match history
+        .top_stories_per_week(since, params.limit.unwrap_or(10).min(HISTORY_LIMIT_MAX))
+        .await
+    {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => {
+            console_error!("[Router] ❌ Query weekly top fails. Error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[worker::send]
+pub async fn get_history_domains(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryParams>,
+) -> impl IntoResponse {
+    console_log!("[Router] Trigger get_history_domains");
+    let Some(history) = HistoryStore::from_env(&state.env) else {
+        return (StatusCode::NOT_FOUND, "History store is not configured").into_response();
+    };
+    let since = history_since(params.days.unwrap_or(30));
+    match history
+        .top_domains(since, params.limit.unwrap_or(20).min(HISTORY_LIMIT_MAX))
+        .await
+    {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => {
+            console_error!("[Router] ❌ Query top domains fails. Error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
 pub async fn fallback_handler(uri: Uri) -> impl IntoResponse {
     console_log!("[Router] Trigger fallback_handler");
     (StatusCode::NOT_FOUND, format!("404 Not Found: {}", uri))
@@ -1,216 +1,448 @@
-use std::fmt::Write;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
 use worker::*;
 
-use crate::config::{KV_TTL_KEY, KV_TTL_VAL, MIN_SCORE_DEFAULT, UNIX_TIME_DEFAULT};
+use crate::config::TRAJECTORY_DEPTH;
 use crate::{
-    api::hn::{fetch_top_items, HackerNewsItem},
-    api::tg::send_message,
-    kvm::{KVManager, KVMeta},
-    utils::tools::encode_base56,
+    api::hn::HackerNewsItem,
+    article::fetch_article_cached,
+    dedup::Dedup,
+    history::{HistoryStore, Snapshot},
+    kvm::{DeliveryRecord, KVMeta},
+    llm::{
+        cache_score, cached_score, provider_from_env, score, summarize, threshold_decision,
+        LlmProvider, ScoringConfig,
+    },
+    notify::dispatch,
+    state::AppState,
+    trajectory::{load_trajectories, record_trajectories},
 };
 
+/// Why an item was or was not picked by a job run.
+#[derive(Clone, Debug, Serialize)]
+pub struct ItemDecision {
+    pub id: u64,
+    pub title: Option<String>,
+    pub score: u64,
+    pub llm_score: Option<f32>,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct JobReport {
+    pub dry_run: bool,
+    pub fetched: usize,
+    /// Items left pending by earlier runs, retried first
+    pub pending: Vec<u64>,
+    pub notify: Vec<ItemDecision>,
+    pub filtered: Vec<ItemDecision>,
+    pub notified: usize,
+    pub deferred: usize,
+    pub failed: Vec<String>,
+}
+
+impl JobReport {
+    fn mark_seen(&mut self, id: u64) {
+        console_log!("[Job TG] Drop id:{} already in dedup index", id);
+        if let Some(pos) = self.notify.iter().position(|d| d.id == id) {
+            let d = self.notify.remove(pos);
+            self.filtered.push(ItemDecision {
+                reason: "already in dedup index".to_string(),
+                ..d
+            });
+        }
+    }
+
+    pub fn into_result(self) -> Result<Self> {
+        if !self.failed.is_empty() {
+            return Err(Error::RustError(format!(
+                "Error message: notify failed for sink:item {:?}",
+                self.failed
+            )));
+        }
+        Ok(self)
+    }
+}
+
 // TODO add shards
-pub async fn run_telegram_job(env: Env, _shards: Option<u16>) -> Result<()> {
+/// With `dry_run` the job only reports its decisions: no KV writes, no
+/// article fetches or summaries, no sends.
+pub async fn run_telegram_job(
+    state: &AppState,
+    _shards: Option<u16>,
+    dry_run: bool,
+) -> Result<JobReport> {
     console_log!("[Job TG] Fetch top stories without shards with Hacker News API");
-    let hn_prefix = "HN-";
-    let ttl_key = KV_TTL_KEY;
-    let ttl_val = KV_TTL_VAL;
-    let kv: KvStore = env.kv("HACKER_NEWS_WORKER_RS")?;
-    let kvm = KVManager::init(kv, hn_prefix, ttl_key, ttl_val).await?;
+    let env = &state.env;
+    // Settings in KV are read on each run, so edits apply without a redeploy
+    let config = &state.effective_config().await?;
+    let hn_prefix = config.kv_prefix.as_str();
+    let kvm = &state.kvm;
+    if !dry_run {
+        kvm.sync_ttl().await?;
+    }
+    let mut job = JobReport {
+        dry_run,
+        ..Default::default()
+    };
     // TODO may need design api error
-    let top_items = fetch_top_items(None)
+    // Ranks past `top_limit` only feed the rank-climb rule, no item fetches
+    let ranked_ids = state
+        .hn
+        .fetch_top_stories(Some(config.top_limit.max(TRAJECTORY_DEPTH)))
         .await
         .map_err(|e| Error::RustError(e.to_string()))?;
+    let top_n = ranked_ids.len().min(config.top_limit.into());
+    let top_items = state
+        .hn
+        .fetch_items(&ranked_ids[..top_n])
+        .await
+        .map_err(|e| Error::RustError(e.to_string()))?;
+    job.fetched = top_items.len();
+    let now = Date::now().as_millis() / 1000;
+    // A dry run sees the same velocities without storing the new point
+    let trajectories = if dry_run {
+        load_trajectories(kvm.kv()).await.map(|mut t| {
+            t.push(&ranked_ids, &top_items, now);
+            t
+        })
+    } else {
+        record_trajectories(kvm.kv(), &ranked_ids, &top_items, now).await
+    }
+    .inspect_err(|e| console_warn!("[Job TG] ⚠️ Record trajectories fails. Error: {}", e))
+    .unwrap_or_default();
+    if !dry_run {
+        if let Some(history) = HistoryStore::from_env(env) {
+            let snapshots = top_items
+                .iter()
+                .map(|i| Snapshot::new(i, now))
+                .collect::<Vec<_>>();
+            match history.record(&snapshots).await {
+                Ok(()) => console_log!("[Job TG] Recorded {} history snapshots", snapshots.len()),
+                Err(e) => console_warn!("[Job TG] ⚠️ Record history fails. Error: {}", e),
+            }
+        }
+    }
+
+    // Without sinks pending entries would never leave that state
+    let notifiers = state.notifiers.build(config);
+    if notifiers.is_empty() && !dry_run {
+        return Err(Error::RustError(
+            "Error message: no notifier configured in Env, nothing to deliver to".to_string(),
+        ));
+    }
 
     // Note: No test for listKeys with setting `onlyOnce` false
-    let raw_cached_ids = kvm.list_keys(Some(hn_prefix), true).await?;
-    let cached_ids = raw_cached_ids
-        .into_iter()
-        .filter_map(|prefixed_id| {
-            let stipped = match prefixed_id.strip_prefix(hn_prefix) {
-                Some(i) => i,
-                None => {
-                    console_warn!("[Job TG] ⚠️ Skip unexpected KV key:{}", &prefixed_id);
-                    return None;
-                }
-            };
-            // parse to u64 and discard the error
-            stipped.parse::<u64>().ok()
-        })
-        .collect::<Vec<u64>>();
+    let raw_cached = kvm.list_keys_meta::<KVMeta>(Some(hn_prefix), true).await?;
+    let mut cached_ids = Vec::with_capacity(raw_cached.len());
+    let mut cached_metas = BTreeMap::new();
+    for (prefixed_id, meta) in raw_cached {
+        let stipped = match prefixed_id.strip_prefix(hn_prefix) {
+            Some(i) => i,
+            None => {
+                console_warn!("[Job TG] ⚠️ Skip unexpected KV key:{}", &prefixed_id);
+                continue;
+            }
+        };
+        // parse to u64 and discard the error
+        let Ok(id) = stipped.parse::<u64>() else {
+            continue;
+        };
+        cached_ids.push(id);
+        if let Some(m) = meta {
+            cached_metas.insert(id, m);
+        }
+    }
     console_log!(
         "[Job TG] Cached Hacker News itme ids (parse to to u64):{:?}",
         cached_ids
     );
 
-    let filtered_items = top_items
-        .into_iter()
-        .filter(|item| {
-            item.score.unwrap_or(0) >= MIN_SCORE_DEFAULT
-                && item.timestamp >= UNIX_TIME_DEFAULT
-                && !cached_ids.contains(&item.item_id)
-        })
-        .collect::<Vec<HackerNewsItem>>();
+    // Items left pending by an earlier run (failed or deferred sink) go first.
+    // Only their records and values are read, a failed read skips the item
+    let pending = kvm.pending_deliveries().await?;
+    let mut payloads = Vec::with_capacity(pending.len());
+    for id in pending {
+        let Some(meta) = cached_metas.remove(&id) else {
+            console_warn!(
+                "[Job TG] ⚠️ Pending id:{} has no cached metadata. Skip.",
+                id
+            );
+            continue;
+        };
+        let record = match kvm.get_delivery(id).await {
+            Ok(Some(record)) => record,
+            Ok(None) => continue,
+            Err(e) => {
+                console_warn!(
+                    "[Job TG] ⚠️ Read delivery id:{} fails. Skip. Error: {}",
+                    id,
+                    e
+                );
+                continue;
+            }
+        };
+        match kvm.get_json::<HackerNewsItem>(kvm.item_key(id)).await {
+            Ok(Some(item)) => payloads.push((item, meta, record)),
+            Ok(None) => console_warn!("[Job TG] ⚠️ Pending id:{} has no cached value. Skip.", id),
+            Err(e) => console_warn!(
+                "[Job TG] ⚠️ Read pending id:{} fails. Skip. Error: {}",
+                id,
+                e
+            ),
+        }
+    }
+    job.pending = payloads.iter().map(|(i, _, _)| i.item_id).collect();
+    if !job.pending.is_empty() {
+        console_log!("[Job TG] Retry pending ids:{:?}", job.pending);
+    }
+
+    let llm = provider_from_env(env);
+    let scoring = llm.as_ref().and(ScoringConfig::from_env(env, config));
+    // A perfect relevance score doubles the HN score, so with scoring enabled
+    // candidates at half the threshold are worth asking the model about
+    let pre_min_score = match scoring {
+        Some(_) => config.min_score / 2,
+        None => config.min_score,
+    };
+    let mut candidates = Vec::with_capacity(top_items.len());
+    for item in top_items {
+        // Rising items skip the score threshold, dedup still applies
+        let rising = trajectories.rising(item.item_id, &config.rising);
+        let min_score = match rising {
+            Some(_) => 0,
+            None => pre_min_score,
+        };
+        match filter_reason(&item, &cached_ids, min_score, config.min_time) {
+            Some(reason) => job.filtered.push(decision(&item, None, reason)),
+            None => candidates.push((item, rising)),
+        }
+    }
+
+    // Scoring stage. Without a score (disabled or provider error) the plain
+    // HN score decides, so an LLM outage never blocks notifying
+    let mut filtered_items = Vec::with_capacity(candidates.len());
+    for (item, rising) in candidates {
+        let llm_score = match (llm.as_deref(), scoring.as_ref()) {
+            (Some(provider), Some(cfg)) => {
+                score_once(kvm.kv(), provider, cfg, &item, dry_run).await
+            }
+            _ => None,
+        };
+        let hn_score = item.score.unwrap_or(0);
+        let label = rising.as_ref().map(|r| r.label());
+        match threshold_decision(
+            hn_score,
+            llm_score,
+            scoring.as_ref(),
+            config.min_score,
+            label.as_deref(),
+        ) {
+            Ok(reason) => {
+                job.notify.push(decision(&item, llm_score, reason));
+                filtered_items.push((item, llm_score, rising));
+            }
+            Err(reason) => {
+                console_log!(
+                    "[Job TG] Drop id:{} score:{} llm_score:{:?}",
+                    item.item_id,
+                    hn_score,
+                    llm_score
+                );
+                job.filtered.push(decision(&item, llm_score, reason));
+            }
+        }
+    }
     console_log!(
         "[Job TG] Filter items, show ids (map to u64):{:?}",
         filtered_items
             .iter()
-            .map(|i| i.item_id)
+            .map(|(i, _, _)| i.item_id)
             .collect::<Vec<u64>>()
     );
-    for item in &filtered_items {
-        // TODO no parallel
-        // TODO make prefix factory
-        let kk = format!("{}{}", hn_prefix, item.item_id);
-        let vv = serde_json::to_string(item)?;
+    // The KV list above may miss keys written elsewhere in the last minute,
+    // the Durable Object index settles it. Known ids are dropped here, the
+    // rest are claimed one by one right before their pending write
+    let dedup = Dedup::from_env(env, config.dedup)?;
+    let ids = filtered_items
+        .iter()
+        .map(|(i, _, _)| i.item_id)
+        .collect::<Vec<u64>>();
+    let seen = dedup.contains(&ids).await?;
+    filtered_items.retain(|(i, _, _)| !seen.contains(&i.item_id));
+    for id in seen {
+        job.mark_seen(id);
+    }
+    if dry_run {
         console_log!(
-            "[Job TG] Try cache id:{} with metadata... and ttl(default).",
-            item.item_id
+            "[Job TG] Dry run. Would notify:{} filtered:{}",
+            job.notify.len(),
+            job.filtered.len()
         );
+        return Ok(job);
+    }
+    let preview_images = config.telegram.as_ref().is_some_and(|t| t.preview_images);
+    for (item, llm_score, rising) in filtered_items {
+        // TODO no parallel
+        // Article stage, needed for the summary and the Telegram preview image
+        let article = if llm.is_some() || preview_images {
+            fetch_article_cached(kvm, &item)
+                .await
+                .inspect_err(|e| {
+                    console_warn!(
+                        "[Job TG] ⚠️ Fetch article id:{} fails. Error: {}",
+                        item.item_id,
+                        e
+                    )
+                })
+                .ok()
+                .flatten()
+        } else {
+            None
+        };
+        // Summarization stage, a failing provider never blocks notifying
+        let llm_summary = match llm.as_deref() {
+            Some(provider) => summarize(provider, &item, article.as_ref().map(|a| a.text.as_str()))
+                .await
+                .inspect(|summary| {
+                    console_log!(
+                        "[Job TG] Summarized id:{} with provider:{} ({} bytes)",
+                        item.item_id,
+                        provider.name(),
+                        summary.len()
+                    )
+                })
+                .inspect_err(|e| {
+                    console_warn!(
+                        "[Job TG] ⚠️ Summarize id:{} fails. Error: {}",
+                        item.item_id,
+                        e
+                    )
+                })
+                .ok(),
+            None => None,
+        };
+        // First phase: cache as pending, flipped to delivered by `dispatch`
+        let kk = kvm.item_key(item.item_id);
+        let vv = serde_json::to_string(&item)?;
         let uuid = uuid::Uuid::new_v4();
         let mut metas = KVMeta::new(uuid);
         metas
-            .with_llm_summary(Some("Test".to_string()))
-            .with_llm_score(Some("Test".to_string()));
-        kvm.create(kk, vv, Some(metas), None).await?;
+            .with_llm_summary(llm_summary)
+            .with_llm_score(llm_score)
+            .with_rising(rising.map(|r| r.label()));
+        // Claim last, so only the two writes below sit between the claim and
+        // a pending entry a later run can retry
+        if dedup.claim(&[item.item_id]).await?.is_empty() {
+            job.mark_seen(item.item_id);
+            continue;
+        }
+        console_log!(
+            "[Job TG] Try cache pending id:{} with metadata... and ttl(default).",
+            item.item_id
+        );
+        // The record goes first, a record without an item is skipped later
+        // while an item without a record would read as delivered
+        let record = DeliveryRecord::default();
+        let staged = match kvm.put_delivery(item.item_id, &record).await {
+            Ok(()) => kvm.create(kk, vv, Some(&metas), None).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = staged {
+            if let Err(re) = dedup.release(&[item.item_id]).await {
+                console_error!(
+                    "[Job TG] ❌ Release claim id:{} fails. Error: {}",
+                    item.item_id,
+                    re
+                );
+            }
+            return Err(e);
+        }
+        payloads.push((item, metas, record));
     }
 
-    notify_all(env, filtered_items, None).await?;
-    Ok(())
+    let report = dispatch(&notifiers, kvm, &mut payloads, &config.link_base).await?;
+    console_log!(
+        "[Job TG] Notified:{} deferred:{} failed:{:?}",
+        report.notified,
+        report.deferred,
+        report.failed
+    );
+    job.notified = report.notified;
+    job.deferred = report.deferred;
+    job.failed = report.failed;
+    // Failed sends are turned into an error by the caller, after the run is logged
+    Ok(job)
 }
 
-async fn notify_all(
-    env: Env,
-    payloads: Vec<HackerNewsItem>,
-    specified_bots: Option<Vec<String>>,
-) -> Result<()> {
-    if let None = specified_bots {
-        console_warn!(
-            "[Notify] ⚠️ notifyAll with specifiedBots (bot list) not implement. Fallback to default bot."
-        );
-    }
-    let tg_token = env
-        .secret("TG_BOT_TOKEN")
-        .map_err(|e| {
-            console_error!(
-                "[Notify] ❌ Error in notifyTg, Telegram bot token missing in Env. Please Check."
+/// Cheap pre-filter before scoring, `None` when the item stays a candidate.
+/// LLM score from the `SCORE-<id>` cache, or from the provider and then cached
+/// unless `dry_run`. Cache errors only cost an extra provider call.
+async fn score_once(
+    kv: &KvStore,
+    provider: &dyn LlmProvider,
+    cfg: &ScoringConfig,
+    item: &HackerNewsItem,
+    dry_run: bool,
+) -> Option<f32> {
+    match cached_score(kv, item.item_id).await {
+        Ok(Some(s)) => {
+            console_log!(
+                "[Job TG] Hit score cache id:{} llm_score:{}",
+                item.item_id,
+                s
             );
+            return Some(s);
+        }
+        Ok(None) => {}
+        Err(e) => console_warn!(
+            "[Job TG] ⚠️ Read score cache id:{} fails. Error: {}",
+            item.item_id,
             e
-        })?
-        .to_string();
-    let tg_chat_id = env.var("TG_CHAT_ID")
-        .map_err(|e| {
-            console_error!("[Notify] ❌ Error in notifyTg, Telegram Chat ID (may use \'@xxx\') missing in Env. Please Check.");
-            e
-        })?
-        .to_string();
-    for p in payloads {
-        console_log!(
-            "[Notify] Title: \"{}\" --- By: {}\n[Notify] Link: {}",
-            &p.title.as_deref().unwrap_or_default(),
-            &p.by,
-            &p.url.as_deref().unwrap_or_default()
-        );
-        notify_tg(&tg_token, &tg_chat_id, &p, None).await?
+        ),
     }
-    Ok(())
-}
-
-async fn notify_tg(
-    tg_token: &str,
-    tg_chat_id: &str,
-    payload: &HackerNewsItem,
-    specified_bot: Option<String>,
-) -> Result<()> {
-    if let None = specified_bot {
-        console_warn!(
-            "[Notify] ⚠️ notifyTg with specified bot not implement. Fallback to default bot."
-        );
+    let s = score(provider, cfg, item)
+        .await
+        .inspect_err(|e| console_warn!("[Job TG] ⚠️ Score id:{} fails. Error: {}", item.item_id, e))
+        .ok()?;
+    console_log!("[Job TG] Scored id:{} llm_score:{}", item.item_id, s);
+    if !dry_run {
+        if let Err(e) = cache_score(kv, item.item_id, s).await {
+            console_warn!(
+                "[Job TG] ⚠️ Cache score id:{} fails. Error: {}",
+                item.item_id,
+                e
+            );
+        }
     }
-    let story_id = payload.item_id.to_string();
-    let short_id = encode_base56(payload.item_id);
-
-    let cc_option = payload.decendants;
-    // Comment url group
-    let hn_url: String =
-        Url::parse_with_params("https://news.ycombinator.com/item/", &[("id", &story_id)])?
-            .to_string();
-    let short_hn_url: String = format!("https://readhacker.news/c/{}", &short_id);
-    // Story url group
-    let story_url: String = payload.url.as_deref().unwrap_or(&hn_url).to_string();
-    let short_story_url: String = payload
-        .url
-        .as_deref()
-        .map(|_| format!("https://readhacker.news/s/{}", &short_id))
-        .unwrap_or(short_hn_url.clone());
-    // Build buttons
-    let buttons = serde_json::json!([
-        {
-            "text": payload.url.as_deref().map_or_else(
-                || "Read",
-                |_| "Read HN",
-            ),
-            "url": story_url,
-        },
-        {
-            "text": cc_option.map_or_else(
-                || "Comments".to_string(),
-                |cc| format!("Comments {}+", cc),
-            ),
-            "url": short_hn_url,
-        },
-    ]);
-    let reply_markup = serde_json::json!({
-        "inline_keyboard": [buttons],
-    });
-
-    // TODO Build 🔥 or ❄️
-    // Build message
-    let msg = build_tg_message(payload, "🦀 ", &short_story_url, &short_hn_url);
-    let res = send_message(tg_token, tg_chat_id, &msg, reply_markup).await?;
-    if !(200..300).contains(&res.status_code()) {
-        console_error!("[Notify] ❌ notifyTg fails. Code: {}.", &res.status_code());
-        return Err(Error::RustError("failed to fetch max item".into()));
-    }
-    Ok(())
+    Some(s)
 }
 
-fn escape_html(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&#39;")
+fn filter_reason(
+    item: &HackerNewsItem,
+    cached_ids: &[u64],
+    min_score: u64,
+    min_time: u64,
+) -> Option<String> {
+    let score = item.score.unwrap_or(0);
+    if cached_ids.contains(&item.item_id) {
+        Some("already cached".to_string())
+    } else if score < min_score {
+        Some(format!("score {} below {}", score, min_score))
+    } else if item.timestamp < min_time {
+        Some(format!("posted at {} before {}", item.timestamp, min_time))
+    } else {
+        None
+    }
 }
 
-fn build_tg_message(
-    payload: &HackerNewsItem,
-    status_emoji: &str,
-    short_story_url: &str,
-    short_hn_url: &str,
-) -> String {
-    let mut msg = String::new();
-    // Add title
-    let title = escape_html(payload.title.as_deref().unwrap_or("Untitled"));
-
-    // Add Score
-    let score_part = payload
-        .score
-        .map(|s| format!("Score: {}+", s))
-        .unwrap_or_default();
-    let by_part = format!("by {}", payload.by);
-
-    write!(&mut msg, "<b>{}</b> {}", title, status_emoji);
-    if score_part.is_empty() {
-        write!(&mut msg, "\n({})", by_part);
-    } else {
-        write!(&mut msg, "\n({} · {})", score_part, by_part);
+fn decision(item: &HackerNewsItem, llm_score: Option<f32>, reason: String) -> ItemDecision {
+    ItemDecision {
+        id: item.item_id,
+        title: item.title.clone(),
+        score: item.score.unwrap_or(0),
+        llm_score,
+        reason,
     }
-    // Add Story and Comments Link
-    write!(
-        &mut msg,
-        "\n\n<b>Link:</b> {}\n<b>Comments:</b> {}",
-        short_story_url, short_hn_url
-    );
-    msg
 }
@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::{
+    config::{Config, ConfigError},
+    keys::SETTINGS_KEY,
+};
+
+// HN serves at most 500 top stories
+const TOP_LIMIT_MAX: u16 = 500;
+
+/// JSON Schema of `Settings`, served by `GET /admin/settings`. `validate`
+/// enforces the same rules.
+pub const SETTINGS_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "Settings",
+  "type": "object",
+  "additionalProperties": false,
+  "properties": {
+    "min_score": { "type": "integer", "minimum": 0 },
+    "min_time": { "type": "integer", "minimum": 0 },
+    "top_limit": { "type": "integer", "minimum": 1, "maximum": 500 },
+    "tg_chat_id": { "type": "string", "pattern": "^(@[A-Za-z0-9_]{5,}|-?[0-9]+)$" },
+    "tg_preview_images": { "type": "boolean" },
+    "link_base": { "type": "string", "pattern": "^https?://" }
+  }
+}"#;
+
+/// Runtime overrides of `Config`, unset fields keep the Env value.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_limit: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tg_chat_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tg_preview_images: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_base: Option<String>,
+}
+
+impl Settings {
+    pub fn validate(&self) -> std::result::Result<(), ConfigError> {
+        let mut errors = Vec::new();
+        if let Some(limit) = self.top_limit {
+            if !(1..=TOP_LIMIT_MAX).contains(&limit) {
+                errors.push(format!(
+                    "top_limit {} is not in 1..={}",
+                    limit, TOP_LIMIT_MAX
+                ));
+            }
+        }
+        if let Some(chat_id) = self.tg_chat_id.as_deref() {
+            let valid = match chat_id.strip_prefix('@') {
+                Some(name) => {
+                    name.len() >= 5 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                }
+                None => {
+                    let digits = chat_id.strip_prefix('-').unwrap_or(chat_id);
+                    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+                }
+            };
+            if !valid {
+                errors.push(format!(
+                    "tg_chat_id {} is neither @username nor a numeric id",
+                    chat_id
+                ));
+            }
+        }
+        if let Some(base) = self.link_base.as_deref() {
+            match Url::parse(base) {
+                Ok(u) if matches!(u.scheme(), "http" | "https") => {}
+                _ => errors.push(format!("link_base {} is not an http(s) url", base)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ConfigError(errors));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, config: &Config) -> Config {
+        let mut c = config.clone();
+        if let Some(v) = self.min_score {
+            c.min_score = v;
+        }
+        if let Some(v) = self.min_time {
+            c.min_time = v;
+        }
+        if let Some(v) = self.top_limit {
+            c.top_limit = v;
+        }
+        if let Some(v) = self.link_base.as_ref() {
+            c.link_base = v.clone();
+        }
+        if let Some(tg) = c.telegram.as_mut() {
+            if let Some(v) = self.tg_chat_id.as_ref() {
+                tg.chat_id = v.clone();
+            }
+            if let Some(v) = self.tg_preview_images {
+                tg.preview_images = v;
+            }
+        }
+        c
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SettingsDoc {
+    /// Bumped on every write, 0 when nothing was stored yet
+    pub version: u64,
+    pub updated_at: u64,
+    pub settings: Settings,
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The stored document moved on since the client read it
+    Conflict(SettingsDoc),
+    Invalid(ConfigError),
+    Worker(Error),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Conflict(cur) => write!(
+                f,
+                "Error message: settings version conflict, current version is {}",
+                cur.version
+            ),
+            SettingsError::Invalid(e) => write!(f, "{}", e),
+            SettingsError::Worker(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<Error> for SettingsError {
+    fn from(e: Error) -> Self {
+        SettingsError::Worker(e)
+    }
+}
+
+impl From<KvError> for SettingsError {
+    fn from(e: KvError) -> Self {
+        SettingsError::Worker(e.into())
+    }
+}
+
+impl From<serde_json::Error> for SettingsError {
+    fn from(e: serde_json::Error) -> Self {
+        SettingsError::Worker(e.into())
+    }
+}
+
+pub async fn load_settings(kv: &KvStore) -> Result<SettingsDoc> {
+    Ok(kv
+        .get(SETTINGS_KEY)
+        .json::<SettingsDoc>()
+        .await?
+        .unwrap_or_default())
+}
+
+/// Store `settings` if the stored version still equals `expected_version`.
+/// KV has no compare-and-swap, so two writers racing within KV's propagation
+/// delay can still both succeed; the version check catches stale editors.
+pub async fn save_settings(
+    kv: &KvStore,
+    expected_version: u64,
+    settings: Settings,
+) -> std::result::Result<SettingsDoc, SettingsError> {
+    settings.validate().map_err(SettingsError::Invalid)?;
+    let current = load_settings(kv).await?;
+    if current.version != expected_version {
+        return Err(SettingsError::Conflict(current));
+    }
+    let doc = SettingsDoc {
+        version: current.version + 1,
+        updated_at: Date::now().as_millis() / 1000,
+        settings,
+    };
+    kv.put(SETTINGS_KEY, serde_json::to_string(&doc)?)?
+        .execute()
+        .await?;
+    console_log!("[Settings] Stored settings version:{}", doc.version);
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(settings: Settings) -> Vec<String> {
+        settings.validate().err().map(|e| e.0).unwrap_or_default()
+    }
+
+    #[test]
+    fn validate_tg_chat_id() {
+        for ok in ["@hn_channel", "@abcde", "123456", "-1001234567890"] {
+            let s = Settings {
+                tg_chat_id: Some(ok.to_string()),
+                ..Default::default()
+            };
+            assert!(errors(s).is_empty(), "{}", ok);
+        }
+        for bad in ["", "@abcd", "@bad-name", "-", "12a", "hn_channel", "--1"] {
+            let s = Settings {
+                tg_chat_id: Some(bad.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(errors(s).len(), 1, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn validate_top_limit_and_link_base() {
+        for (limit, ok) in [
+            (0, false),
+            (1, true),
+            (TOP_LIMIT_MAX, true),
+            (TOP_LIMIT_MAX + 1, false),
+        ] {
+            let s = Settings {
+                top_limit: Some(limit),
+                ..Default::default()
+            };
+            assert_eq!(errors(s).is_empty(), ok, "{}", limit);
+        }
+        for (base, ok) in [
+            ("https://readhacker.news", true),
+            ("http://localhost:8787", true),
+            ("ftp://example.com", false),
+            ("readhacker.news", false),
+        ] {
+            let s = Settings {
+                link_base: Some(base.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(errors(s).is_empty(), ok, "{}", base);
+        }
+    }
+
+    #[test]
+    fn validate_reports_every_error() {
+        let s = Settings {
+            top_limit: Some(0),
+            tg_chat_id: Some("@x".to_string()),
+            link_base: Some("nope".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(errors(s).len(), 3);
+    }
+
+    #[test]
+    fn apply_keeps_unset_fields() {
+        let config = Config::mock();
+        let applied = Settings {
+            min_score: Some(300),
+            ..Default::default()
+        }
+        .apply(&config);
+        assert_eq!(applied.min_score, 300);
+        assert_eq!(applied.min_time, config.min_time);
+        assert_eq!(applied.top_limit, config.top_limit);
+        assert_eq!(applied.link_base, config.link_base);
+        let tg = applied.telegram.unwrap();
+        assert_eq!(tg.chat_id, "@hn_channel");
+        assert!(!tg.preview_images);
+    }
+
+    #[test]
+    fn apply_overrides_telegram_only_when_enabled() {
+        let settings = Settings {
+            tg_chat_id: Some("@other_channel".to_string()),
+            tg_preview_images: Some(true),
+            ..Default::default()
+        };
+        let tg = settings.apply(&Config::mock()).telegram.unwrap();
+        assert_eq!(tg.chat_id, "@other_channel");
+        assert!(tg.preview_images);
+
+        let disabled = Config {
+            telegram: None,
+            ..Config::mock()
+        };
+        assert!(settings.apply(&disabled).telegram.is_none());
+    }
+
+    #[test]
+    fn schema_matches_settings() {
+        let schema: serde_json::Value = serde_json::from_str(SETTINGS_SCHEMA).unwrap();
+        let full = Settings {
+            min_score: Some(0),
+            min_time: Some(0),
+            top_limit: Some(1),
+            tg_chat_id: Some("@hn_channel".to_string()),
+            tg_preview_images: Some(false),
+            link_base: Some("https://readhacker.news".to_string()),
+        };
+        let fields = serde_json::to_value(full).unwrap();
+        let mut fields = fields.as_object().unwrap().keys().collect::<Vec<_>>();
+        let mut properties = schema["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>();
+        fields.sort();
+        properties.sort();
+        assert_eq!(fields, properties);
+        assert_eq!(schema["properties"]["top_limit"]["maximum"], TOP_LIMIT_MAX);
+    }
+}
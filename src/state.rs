@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use worker::{console_warn, wasm_bindgen_futures, Context, Env, Result};
+
+use crate::{
+    api::hn::HnClient,
+    config::{Config, ConfigError},
+    keys::KV_TTL_KEY,
+    kvm::KVManager,
+    notify::NotifierRegistry,
+    settings::load_settings,
+};
+
+/// Per-request state handed to axum handlers through `State`.
+#[derive(Clone)]
+pub struct AppState {
+    pub env: Env,
+    pub ctx: Option<Arc<Context>>,
+    pub kvm: KVManager,
+    pub hn: HnClient,
+    pub config: Arc<Config>,
+    pub notifiers: NotifierRegistry,
+    /// Problems found loading `config`, whose invalid fields hold defaults
+    pub config_error: Option<ConfigError>,
+}
+
+impl AppState {
+    /// Cheap to build: no KV reads or writes happen here. Config problems are
+    /// kept in `config_error` rather than failing, only a missing KV binding fails.
+    pub fn from_env(env: Env) -> Result<Self> {
+        let (config, config_error) = Config::from_env(&env);
+        if let Some(e) = config_error.as_ref() {
+            console_warn!("[State] ⚠️ {}", e);
+        }
+        let kv = env.kv(&config.kv_binding)?;
+        Ok(Self {
+            kvm: KVManager::new(
+                kv,
+                config.kv_prefix.clone(),
+                KV_TTL_KEY.to_string(),
+                config.kv_ttl,
+            ),
+            hn: HnClient::default(),
+            notifiers: NotifierRegistry::new(env.clone()),
+            config,
+            config_error,
+            ctx: None,
+            env,
+        })
+    }
+
+    /// `config`, or every problem found loading it. For paths that must not
+    /// run on defaults, like jobs.
+    pub fn checked_config(&self) -> std::result::Result<&Arc<Config>, ConfigError> {
+        match self.config_error.as_ref() {
+            Some(e) => Err(e.clone()),
+            None => Ok(&self.config),
+        }
+    }
+
+    /// `Config` with the KV settings document applied on top.
+    pub async fn effective_config(&self) -> Result<Arc<Config>> {
+        let doc = load_settings(self.kvm.kv()).await?;
+        if doc.version == 0 {
+            return Ok(self.config.clone());
+        }
+        Ok(Arc::new(doc.settings.apply(&self.config)))
+    }
+
+    pub fn with_context(mut self, ctx: Context) -> Self {
+        self.ctx = Some(Arc::new(ctx));
+        self
+    }
+
+    /// Run `fut` after the response is sent. Outside a fetch it is only spawned.
+    pub fn wait_until<F>(&self, fut: F)
+    where
+        F: std::future::Future<Output = ()> + 'static,
+    {
+        match self.ctx.as_ref() {
+            Some(ctx) => ctx.wait_until(fut),
+            None => wasm_bindgen_futures::spawn_local(fut),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{cell::RefCell, collections::BTreeMap, time::Duration};
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::{
+    config::{CLICKS_FLUSH_SECS, CLICKS_TTL},
+    keys::CLICKS_PREFIX,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum ClickKind {
+    Story,
+    Comments,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ClickStats {
+    pub story: u64,
+    pub comments: u64,
+}
+
+impl ClickStats {
+    pub fn total(&self) -> u64 {
+        self.story + self.comments
+    }
+}
+
+/// `DNT: 1` or `Sec-GPC: 1` opt the request out of click tracking.
+pub fn do_not_track(headers: &HeaderMap) -> bool {
+    ["DNT", "Sec-GPC"]
+        .iter()
+        .any(|h| headers.get(*h).is_some_and(|v| v.as_bytes() == b"1"))
+}
+
+thread_local! {
+    static BUFFER: RefCell<ClickBuffer> = RefCell::new(ClickBuffer::default());
+}
+
+/// Click increments of this isolate not yet written to KV. Each flush does
+/// one read-modify-write per item instead of one per click, keeping well
+/// under KV's one write per second per key.
+#[derive(Debug, Default)]
+pub struct ClickBuffer {
+    pending: BTreeMap<u64, ClickStats>,
+    flush_scheduled: bool,
+}
+
+impl ClickBuffer {
+    /// Returns `true` for the first click since the last flush, the caller
+    /// then schedules the next flush.
+    pub fn add(&mut self, id: u64, kind: ClickKind) -> bool {
+        let stats = self.pending.entry(id).or_default();
+        match kind {
+            ClickKind::Story => stats.story += 1,
+            ClickKind::Comments => stats.comments += 1,
+        }
+        !std::mem::replace(&mut self.flush_scheduled, true)
+    }
+
+    pub fn get(&self, id: u64) -> ClickStats {
+        self.pending.get(&id).cloned().unwrap_or_default()
+    }
+
+    pub fn take(&mut self) -> BTreeMap<u64, ClickStats> {
+        self.flush_scheduled = false;
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Put back increments a flush could not write, the next click
+    /// schedules another flush.
+    pub fn restore(&mut self, id: u64, stats: ClickStats) {
+        self.pending.entry(id).or_default().merge(&stats);
+    }
+}
+
+impl ClickStats {
+    fn merge(&mut self, other: &ClickStats) {
+        self.story += other.story;
+        self.comments += other.comments;
+    }
+}
+
+/// Stored counters plus the unflushed clicks of this isolate.
+pub async fn get_clicks(kv: &KvStore, id: u64) -> Result<ClickStats> {
+    let mut stats = stored_clicks(kv, id).await?;
+    stats.merge(&BUFFER.with(|b| b.borrow().get(id)));
+    Ok(stats)
+}
+
+async fn stored_clicks(kv: &KvStore, id: u64) -> Result<ClickStats> {
+    let key = format!("{}{}", CLICKS_PREFIX, id);
+    Ok(kv.get(&key).json::<ClickStats>().await?.unwrap_or_default())
+}
+
+/// Count a click in the isolate buffer. Returns `true` when the caller
+/// should schedule `flush_clicks` after `CLICKS_FLUSH_SECS`.
+pub fn record_click(id: u64, kind: ClickKind) -> bool {
+    BUFFER.with(|b| b.borrow_mut().add(id, kind))
+}
+
+/// Wait `CLICKS_FLUSH_SECS` to batch further clicks, then add the buffered
+/// increments to KV. Counters updated by other isolates at the same moment
+/// may still lose a batch, KV has no atomic increment.
+pub async fn flush_clicks(kv: &KvStore) -> Result<()> {
+    Delay::from(Duration::from_secs(CLICKS_FLUSH_SECS)).await;
+    let pending = BUFFER.with(|b| b.borrow_mut().take());
+    let mut result = Ok(());
+    for (id, clicks) in pending {
+        let written = async {
+            let mut stats = stored_clicks(kv, id).await?;
+            stats.merge(&clicks);
+            let key = format!("{}{}", CLICKS_PREFIX, id);
+            kv.put(&key, serde_json::to_string(&stats)?)?
+                .expiration_ttl(CLICKS_TTL)
+                .execute()
+                .await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+        if let Err(e) = written {
+            BUFFER.with(|b| b.borrow_mut().restore(id, clicks));
+            result = Err(e);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_aggregates_until_taken() {
+        let mut buffer = ClickBuffer::default();
+        assert!(buffer.add(1, ClickKind::Story));
+        assert!(!buffer.add(1, ClickKind::Story));
+        assert!(!buffer.add(1, ClickKind::Comments));
+        assert!(!buffer.add(2, ClickKind::Comments));
+        assert_eq!(buffer.get(1).total(), 3);
+
+        let taken = buffer.take();
+        assert_eq!(taken[&1].story, 2);
+        assert_eq!(taken[&2].comments, 1);
+        assert_eq!(buffer.get(1).total(), 0);
+        // The next click after a flush schedules the next one
+        assert!(buffer.add(1, ClickKind::Story));
+    }
+
+    #[test]
+    fn buffer_keeps_failed_increments() {
+        let mut buffer = ClickBuffer::default();
+        buffer.add(1, ClickKind::Story);
+        let taken = buffer.take();
+        buffer.add(1, ClickKind::Comments);
+        buffer.restore(1, taken[&1].clone());
+        let stats = buffer.get(1);
+        assert_eq!((stats.story, stats.comments), (1, 1));
+    }
+}
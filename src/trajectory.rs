@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use worker::*;
+
+use crate::{
+    api::hn::HackerNewsItem,
+    config::{
+        RisingRule, RISING_MIN_SPAN_SECS, RISING_TOP_RANK, RISING_WINDOW_SECS, TRAJECTORY_DEPTH,
+        TRAJECTORY_POINTS_MAX, TRAJECTORY_RETENTION_SECS,
+    },
+    keys::TRAJECTORY_KEY,
+};
+
+/// Rank of an item seen in the top list by one run, with its score and
+/// comment count when it was within the fetched `TOP_LIMIT`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Point {
+    /// Unix seconds
+    pub at: u64,
+    pub score: Option<u64>,
+    pub descendants: Option<u64>,
+    /// 1-based position in the top list
+    pub rank: u32,
+}
+
+/// Velocity of an item over the rising window.
+#[derive(Clone, Debug, Serialize)]
+pub struct Rising {
+    /// `None` unless both ends of the window were fetched with a score
+    pub points_per_min: Option<f64>,
+    pub rank_from: u32,
+    pub rank_to: u32,
+    pub mins: u64,
+}
+
+impl Rising {
+    pub fn label(&self) -> String {
+        let climb = format!(
+            "#{} → #{} in {} min",
+            self.rank_from, self.rank_to, self.mins
+        );
+        match self.points_per_min {
+            Some(ppm) => format!("{:.1} pts/min, {}", ppm, climb),
+            None => climb,
+        }
+    }
+}
+
+/// Recent points of every item seen in the top list, oldest first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Trajectories {
+    pub items: BTreeMap<u64, Vec<Point>>,
+}
+
+impl Trajectories {
+    pub fn points(&self, id: u64) -> &[Point] {
+        self.items.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// `Some` when the latest point of `id` beats `rule` against the oldest
+    /// point within the rising window.
+    pub fn rising(&self, id: u64, rule: &RisingRule) -> Option<Rising> {
+        let points = self.points(id);
+        let last = points.last()?;
+        let window_start = last.at.saturating_sub(RISING_WINDOW_SECS);
+        let first = points.iter().find(|p| p.at >= window_start)?;
+        let span = last.at - first.at;
+        if span < RISING_MIN_SPAN_SECS {
+            return None;
+        }
+        // Items climbing from below `TOP_LIMIT` have no score yet at the start,
+        // measure from their first scored point instead
+        let points_per_min = points
+            .iter()
+            .filter(|p| p.at >= window_start && last.at - p.at >= RISING_MIN_SPAN_SECS)
+            .find_map(|p| Some((p.at, p.score?)))
+            .zip(last.score)
+            .map(|((at, from), to)| to.saturating_sub(from) as f64 * 60.0 / (last.at - at) as f64);
+        let climb = first.rank.saturating_sub(last.rank);
+        let fast =
+            rule.points_per_min > 0.0 && points_per_min.is_some_and(|v| v >= rule.points_per_min);
+        let climbing =
+            rule.rank_climb > 0 && climb >= rule.rank_climb && last.rank <= RISING_TOP_RANK;
+        (fast || climbing).then_some(Rising {
+            points_per_min,
+            rank_from: first.rank,
+            rank_to: last.rank,
+            mins: span / 60,
+        })
+    }
+
+    /// Append one point per id, ranked by position in `ranked_ids` and scored
+    /// from `top_items` where fetched, then drop points older than the
+    /// retention window.
+    pub fn push(&mut self, ranked_ids: &[u64], top_items: &[HackerNewsItem], now: u64) {
+        let depth = ranked_ids.len().min(TRAJECTORY_DEPTH.into());
+        for (i, &id) in ranked_ids[..depth].iter().enumerate() {
+            let points = self.items.entry(id).or_default();
+            // A retried run within the same second must not double the point
+            if points.last().is_some_and(|p| p.at == now) {
+                continue;
+            }
+            let item = top_items.iter().find(|item| item.item_id == id);
+            points.push(Point {
+                at: now,
+                score: item.and_then(|i| i.score),
+                descendants: item.and_then(|i| i.decendants),
+                rank: i as u32 + 1,
+            });
+        }
+        let cutoff = now.saturating_sub(TRAJECTORY_RETENTION_SECS);
+        self.items.retain(|_, points| {
+            points.retain(|p| p.at >= cutoff);
+            if points.len() > TRAJECTORY_POINTS_MAX {
+                points.drain(..points.len() - TRAJECTORY_POINTS_MAX);
+            }
+            !points.is_empty()
+        });
+    }
+}
+
+// One document for all items, so a run costs a single KV read and write
+pub async fn load_trajectories(kv: &KvStore) -> Result<Trajectories> {
+    Ok(kv
+        .get(TRAJECTORY_KEY)
+        .json::<Trajectories>()
+        .await?
+        .unwrap_or_default())
+}
+
+/// Add a snapshot of the current top list. Only the job writes it, under
+/// its lease, so the read-modify-write does not race.
+pub async fn record_trajectories(
+    kv: &KvStore,
+    ranked_ids: &[u64],
+    top_items: &[HackerNewsItem],
+    now: u64,
+) -> Result<Trajectories> {
+    let mut trajectories = load_trajectories(kv).await?;
+    trajectories.push(ranked_ids, top_items, now);
+    kv.put(TRAJECTORY_KEY, serde_json::to_string(&trajectories)?)?
+        .expiration_ttl(TRAJECTORY_RETENTION_SECS)
+        .execute()
+        .await?;
+    console_log!(
+        "[Trajectory] Recorded {} ranks, tracking {}",
+        ranked_ids.len(),
+        trajectories.items.len()
+    );
+    Ok(trajectories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULE: RisingRule = RisingRule {
+        points_per_min: 2.0,
+        rank_climb: 20,
+    };
+
+    // (minutes, score, rank) points of item 1
+    fn trajectories(points: &[(u64, Option<u64>, u32)]) -> Trajectories {
+        let points = points
+            .iter()
+            .map(|&(min, score, rank)| Point {
+                at: 1_760_000_000 + min * 60,
+                score,
+                descendants: None,
+                rank,
+            })
+            .collect();
+        Trajectories {
+            items: BTreeMap::from([(1, points)]),
+        }
+    }
+
+    #[test]
+    fn rank_climb_into_top_fires() {
+        let t = trajectories(&[(0, None, 40), (10, Some(60), 18), (20, Some(70), 5)]);
+        let r = t.rising(1, &RULE).unwrap();
+        assert_eq!((r.rank_from, r.rank_to, r.mins), (40, 5, 20));
+        // Measured from the first scored point, 10 points in 10 minutes
+        assert_eq!(r.points_per_min, Some(1.0));
+        assert_eq!(r.label(), "1.0 pts/min, #40 → #5 in 20 min");
+    }
+
+    #[test]
+    fn small_or_shallow_climb_misses() {
+        let small = trajectories(&[(0, Some(50), 20), (20, Some(60), 5)]);
+        assert!(small.rising(1, &RULE).is_none());
+        let shallow = trajectories(&[(0, None, 50), (20, Some(60), 15)]);
+        assert!(shallow.rising(1, &RULE).is_none());
+    }
+
+    #[test]
+    fn points_per_min_fires() {
+        let t = trajectories(&[(0, Some(10), 8), (10, Some(40), 6), (20, Some(70), 4)]);
+        let r = t.rising(1, &RULE).unwrap();
+        assert_eq!(r.points_per_min, Some(3.0));
+    }
+
+    #[test]
+    fn window_ignores_older_points() {
+        let t = trajectories(&[(0, Some(0), 60), (30, Some(100), 30), (50, Some(110), 28)]);
+        assert!(t.rising(1, &RULE).is_none());
+    }
+
+    #[test]
+    fn span_below_minimum_is_ignored() {
+        let t = trajectories(&[(0, Some(10), 40), (4, Some(200), 2)]);
+        assert!(t.rising(1, &RULE).is_none());
+    }
+
+    #[test]
+    fn zero_thresholds_disable_rules() {
+        let t = trajectories(&[(0, Some(10), 40), (20, Some(200), 2)]);
+        let no_velocity = RisingRule {
+            points_per_min: 0.0,
+            ..RULE
+        };
+        assert!(t.rising(1, &no_velocity).is_some());
+        let no_climb = RisingRule {
+            rank_climb: 0,
+            ..RULE
+        };
+        assert!(t.rising(1, &no_climb).is_some());
+        let off = RisingRule {
+            points_per_min: 0.0,
+            rank_climb: 0,
+        };
+        assert!(t.rising(1, &off).is_none());
+    }
+
+    #[test]
+    fn push_ranks_ids_and_scores_fetched_items() {
+        let mut t = Trajectories::default();
+        let ids = (1..=80).collect::<Vec<u64>>();
+        t.push(&ids, &[], 1_760_000_000);
+        assert_eq!(t.items.len(), TRAJECTORY_DEPTH as usize);
+        assert_eq!(t.points(60)[0].rank, 60);
+        assert!(t.points(1)[0].score.is_none());
+        // Points past the retention window are dropped
+        t.push(
+            &ids[..1],
+            &[],
+            1_760_000_000 + TRAJECTORY_RETENTION_SECS + 1,
+        );
+        assert_eq!(t.items.len(), 1);
+    }
+}
@@ -1,2 +1,6 @@
+pub mod rate_limit;
+pub mod short_id;
 pub mod tools;
-pub use tools::*;
\ No newline at end of file
+pub use rate_limit::*;
+pub use short_id::*;
+pub use tools::*;
@@ -0,0 +1,81 @@
+/// Classic token bucket: `capacity` burst, refilled continuously at
+/// `refill_per_sec`. Time is passed in by the caller (unix millis).
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated_ms: u64,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, refill_per_sec: f64, now_ms: u64) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill_per_sec,
+            tokens: capacity as f64,
+            updated_ms: now_ms,
+        }
+    }
+
+    /// Bucket allowing `per_minute` events per minute with the same burst.
+    pub fn per_minute(per_minute: u32, now_ms: u64) -> Self {
+        Self::new(per_minute, per_minute as f64 / 60.0, now_ms)
+    }
+
+    fn refill(&mut self, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.updated_ms) as f64 / 1000.0;
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated_ms = self.updated_ms.max(now_ms);
+    }
+
+    /// Take one token, or return how many millis to wait until one is available.
+    pub fn try_acquire(&mut self, now_ms: u64) -> Result<(), u64> {
+        self.refill(now_ms);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err((missing / self.refill_per_sec * 1000.0).ceil() as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_wait_for_refill() {
+        let mut bucket = TokenBucket::per_minute(20, 0);
+        for _ in 0..20 {
+            assert_eq!(bucket.try_acquire(0), Ok(()));
+        }
+        // One token every 3s at 20 per minute
+        assert_eq!(bucket.try_acquire(0), Err(3000));
+        // Rounded up, so waiting the returned time always yields a token
+        let wait = bucket.try_acquire(1000).unwrap_err();
+        assert!((2000..=2001).contains(&wait), "{}", wait);
+        assert_eq!(bucket.try_acquire(3000), Ok(()));
+        assert_eq!(bucket.try_acquire(3000), Err(3000));
+    }
+
+    #[test]
+    fn refill_is_capped_at_capacity() {
+        let mut bucket = TokenBucket::new(2, 1.0, 0);
+        assert_eq!(bucket.try_acquire(0), Ok(()));
+        assert_eq!(bucket.try_acquire(0), Ok(()));
+        assert_eq!(bucket.try_acquire(60_000), Ok(()));
+        assert_eq!(bucket.try_acquire(60_000), Ok(()));
+        assert_eq!(bucket.try_acquire(60_000), Err(1000));
+    }
+
+    #[test]
+    fn clock_going_back_does_not_refill() {
+        let mut bucket = TokenBucket::new(1, 1.0, 10_000);
+        assert_eq!(bucket.try_acquire(10_000), Ok(()));
+        assert_eq!(bucket.try_acquire(5_000), Err(1000));
+        assert_eq!(bucket.try_acquire(10_500), Err(500));
+    }
+}
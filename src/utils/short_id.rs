@@ -0,0 +1,85 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::tools::{decode_base56, encode_base56, Base56Error};
+
+/// Hacker News item id in its base56 short-link form, e.g. `/s/{code}`.
+/// Deserializes from the code, so `Path<ShortId>` rejects bad links up front.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShortId(u64);
+
+impl ShortId {
+    pub fn new(id: u64) -> Self {
+        Self(id)
+    }
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for ShortId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<ShortId> for u64 {
+    fn from(s: ShortId) -> Self {
+        s.0
+    }
+}
+
+impl FromStr for ShortId {
+    type Err = Base56Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        decode_base56(s).map(Self)
+    }
+}
+
+impl fmt::Display for ShortId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_base56(self.0))
+    }
+}
+
+impl Serialize for ShortId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ShortId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_id_round_trips_from_str() {
+        for id in [0, 1, 8863, 42_000_000, u64::MAX] {
+            let code = ShortId::new(id).to_string();
+            assert_eq!(code.parse::<ShortId>(), Ok(ShortId::new(id)));
+        }
+    }
+
+    #[test]
+    fn short_id_round_trips_serde() {
+        let id = ShortId::new(42_000_000);
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{}\"", encode_base56(42_000_000)));
+        assert_eq!(serde_json::from_str::<ShortId>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn short_id_rejects_bad_codes() {
+        assert!(serde_json::from_str::<ShortId>("\"\"").is_err());
+        assert!(serde_json::from_str::<ShortId>("\"abc0\"").is_err());
+        assert!(serde_json::from_str::<ShortId>("42").is_err());
+    }
+}
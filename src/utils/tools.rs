@@ -2,15 +2,241 @@ const ALPHABET: &[u8; 56] = b"23456789abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRST
 const BASE: u64 = 56;
 
 pub fn encode_base56(mut n: u64) -> String {
-  if n == 0 {
-    return (ALPHABET[0] as char).to_string();
-  }
-  let mut buf = Vec::new();
-  while n > 0 {
-    let i = (n% BASE) as usize;
-    buf.push(ALPHABET[i]);
-    n /= BASE;
-  }
-  buf.reverse();
-  String::from_utf8(buf).unwrap()
-}
\ No newline at end of file
+    if n == 0 {
+        return (ALPHABET[0] as char).to_string();
+    }
+    let mut buf = Vec::new();
+    while n > 0 {
+        let i = (n % BASE) as usize;
+        buf.push(ALPHABET[i]);
+        n /= BASE;
+    }
+    buf.reverse();
+    String::from_utf8(buf).unwrap()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base56Error {
+    Empty,
+    InvalidChar { pos: usize, c: char },
+    Overflow,
+}
+
+impl std::fmt::Display for Base56Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Base56Error::Empty => write!(f, "Error message: empty base56 string"),
+            Base56Error::InvalidChar { pos, c } => {
+                write!(f, "Error message: invalid base56 char {:?} at {}", c, pos)
+            }
+            Base56Error::Overflow => write!(f, "Error message: base56 value overflows u64"),
+        }
+    }
+}
+
+impl std::error::Error for Base56Error {}
+
+/// Inverse of `encode_base56`. Leading `2`s (the zero digit) are accepted.
+pub fn decode_base56(s: &str) -> Result<u64, Base56Error> {
+    if s.is_empty() {
+        return Err(Base56Error::Empty);
+    }
+    let mut n: u64 = 0;
+    for (pos, c) in s.char_indices() {
+        let i = ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or(Base56Error::InvalidChar { pos, c })? as u64;
+        n = n
+            .checked_mul(BASE)
+            .and_then(|n| n.checked_add(i))
+            .ok_or(Base56Error::Overflow)?;
+    }
+    Ok(n)
+}
+
+/// Compare secrets without leaking the position of the first mismatch.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn hmac_sha256_hex(secret: &[u8], msg: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    let mut mac =
+        Hmac::<sha2::Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(msg);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Truncate `s` on grapheme boundaries so that `measure` summed over the kept
+/// graphemes plus the trailing ellipsis stays within `max`.
+pub fn truncate_graphemes(s: &str, max: usize, measure: impl Fn(&str) -> usize) -> String {
+    use unicode_segmentation::UnicodeSegmentation;
+    const ELLIPSIS: &str = "…";
+    if s.graphemes(true).map(&measure).sum::<usize>() <= max {
+        return s.to_string();
+    }
+    let budget = max.saturating_sub(measure(ELLIPSIS));
+    let mut used = 0;
+    let mut out = String::new();
+    for g in s.graphemes(true) {
+        let w = measure(g);
+        if used + w > budget {
+            break;
+        }
+        used += w;
+        out.push_str(g);
+    }
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    out.push_str(ELLIPSIS);
+    out
+}
+
+/// Read a secret, falling back to a plain var. Empty values count as unset.
+pub fn env_string(env: &worker::Env, name: &str) -> Option<String> {
+    env.secret(name)
+        .or_else(|_| env.var(name))
+        .ok()
+        .map(|v| v.to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Drop tags and decode the few entities HN uses, enough for prompts and previews.
+pub fn strip_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_tag = false;
+    for c in s.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                out.push(' ');
+            }
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    let decoded = out
+        .replace("&#x27;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&#39;", "'")
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // splitmix64, a fixed seed keeps the sweep reproducible
+    fn next(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn round_trip(n: u64) {
+        assert_eq!(decode_base56(&encode_base56(n)), Ok(n), "n = {}", n);
+    }
+
+    #[test]
+    fn base56_round_trips_edges() {
+        for n in [0, 1, 55, 56, 57, u64::MAX - 1, u64::MAX] {
+            round_trip(n);
+        }
+        let mut p: u64 = 1;
+        while let Some(next) = p.checked_mul(BASE) {
+            round_trip(p - 1);
+            round_trip(p);
+            round_trip(p + 1);
+            p = next;
+        }
+        round_trip(p);
+    }
+
+    #[test]
+    fn base56_round_trips_random_sweep() {
+        let mut state = 0x5EED;
+        for _ in 0..100_000 {
+            let n = next(&mut state);
+            // Spread over every magnitude, not just the 11-digit codes
+            round_trip(n >> (n % 64));
+        }
+    }
+
+    #[test]
+    fn base56_accepts_leading_zero_digits() {
+        assert_eq!(decode_base56("222"), Ok(0));
+        assert_eq!(decode_base56("23"), Ok(1));
+    }
+
+    #[test]
+    fn base56_rejects_empty() {
+        assert_eq!(decode_base56(""), Err(Base56Error::Empty));
+    }
+
+    #[test]
+    fn base56_rejects_invalid_chars() {
+        for (s, pos, c) in [
+            ("0", 0, '0'),
+            ("ab1", 2, '1'),
+            ("l", 0, 'l'),
+            ("xI", 1, 'I'),
+            ("O", 0, 'O'),
+            ("a-b", 1, '-'),
+            ("aé", 1, 'é'),
+        ] {
+            assert_eq!(
+                decode_base56(s),
+                Err(Base56Error::InvalidChar { pos, c }),
+                "s = {:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn base56_rejects_overflow() {
+        let max = encode_base56(u64::MAX);
+        assert_eq!(
+            decode_base56(&format!("{}2", max)),
+            Err(Base56Error::Overflow)
+        );
+        assert_eq!(decode_base56(&"Z".repeat(12)), Err(Base56Error::Overflow));
+    }
+
+    #[test]
+    fn truncate_graphemes_keeps_short_input() {
+        assert_eq!(truncate_graphemes("héllo", 5, |_| 1), "héllo");
+        assert_eq!(truncate_graphemes("", 0, |_| 1), "");
+    }
+
+    #[test]
+    fn truncate_graphemes_never_splits_a_grapheme() {
+        // Family emoji is one grapheme of 7 chars and 25 bytes
+        let s = "ab👨‍👩‍👧‍👦cd";
+        assert_eq!(truncate_graphemes(s, 4, |_| 1), "ab👨‍👩‍👧‍👦…");
+        assert_eq!(truncate_graphemes(s, 3, |_| 1), "ab…");
+        assert_eq!(truncate_graphemes(s, 29, |g| g.len()), s);
+        assert_eq!(truncate_graphemes(s, 28, |g| g.len()), "ab…");
+    }
+
+    #[test]
+    fn truncate_graphemes_fits_measure_with_ellipsis() {
+        let s = "日本語のテキスト".repeat(10);
+        let out = truncate_graphemes(&s, 20, |g| g.len());
+        assert!(out.len() <= 20, "{}", out);
+        assert_eq!(out, "日本語のテ…");
+        // Trailing whitespace before the ellipsis is dropped
+        assert_eq!(truncate_graphemes("one two three", 5, |_| 1), "one…");
+    }
+}